@@ -0,0 +1,142 @@
+use crate::sketch::bench::{BenchConfig, BenchResults};
+use crate::sketch::distribution::InputDistribution;
+use crate::sketch::headless::SortRun;
+use crate::sketch::methods::SortMethod;
+use crate::sketch::trace_io::TraceFormat;
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Instant;
+use strum::IntoEnumIterator;
+
+/// Visualise sorting algorithms. With no subcommand the window opens.
+#[derive(Parser)]
+#[command(name = "sort_bin")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Sort one input and print how many operations it took
+    Run(Input),
+    /// Run algorithms over a sweep of lengths and distributions, printing operation counts and
+    /// the growth model (n, n log n or n²) that fits each count best
+    Bench {
+        /// Algorithms to run, all of them if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        algorithms: Vec<SortMethod>,
+        /// Input distributions, all of them if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        distributions: Vec<InputDistribution>,
+        /// Lengths to sweep, powers of two from 16 to 1024 if omitted
+        #[arg(short, long, value_delimiter = ',')]
+        lengths: Vec<usize>,
+        #[arg(short, long, default_value_t = 0)]
+        seed: u64,
+        #[arg(short, long, value_enum, default_value_t = BenchFormat::Csv)]
+        format: BenchFormat,
+    },
+    /// Sort one input and write its trace to a file
+    Export {
+        #[command(flatten)]
+        input: Input,
+        /// json, csv or binary
+        #[arg(short, long, default_value = "json")]
+        format: TraceFormat,
+        /// Defaults to `<algorithm>_<length>.<extension>`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the algorithm and input distribution names the other subcommands accept
+    ListAlgorithms,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum BenchFormat {
+    /// One row of counts per run
+    Csv,
+    /// A table of counts and a table of fitted growth models
+    Markdown,
+}
+
+#[derive(Args)]
+pub struct Input {
+    #[arg(short, long, default_value = "quick")]
+    algorithm: SortMethod,
+    #[arg(short, long, default_value = "shuffled")]
+    distribution: InputDistribution,
+    #[arg(short, long, default_value_t = 256)]
+    length: usize,
+    /// Seeds the input, the same seed gives the same input
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl Input {
+    fn run(&self) -> Result<SortRun> {
+        SortRun::new(self.algorithm, self.distribution, self.length, self.seed)
+    }
+}
+
+pub fn execute(command: Command) -> Result<()> {
+    match command {
+        Command::Run(input) => {
+            let start = Instant::now();
+            let run = input.run()?;
+            let elapsed = start.elapsed();
+            let counts = run.counts();
+            println!("algorithm     {}", input.algorithm.name());
+            println!("distribution  {}", input.distribution);
+            println!("length        {}", input.length);
+            println!("comparisons   {}", counts.comparisons);
+            println!("reads         {}", counts.reads);
+            println!("writes        {}", counts.writes);
+            println!("swaps         {}", counts.swaps);
+            println!("total         {}", counts.total());
+            println!("time          {:.3} ms", elapsed.as_secs_f64() * 1000.0);
+            let order = if run.list.is_stable() { "kept their order" } else { "were reordered" };
+            println!("stable        {} (equal values {} in this run)", input.algorithm.is_stable(), order);
+            if !run.list.is_sorted() {
+                bail!("{} did not sort the input", input.algorithm.name());
+            }
+        }
+        Command::Bench { algorithms, distributions, lengths, seed, format } => {
+            let mut config = BenchConfig { seed, ..Default::default() };
+            if !algorithms.is_empty() {
+                config.algorithms = algorithms;
+            }
+            if !distributions.is_empty() {
+                config.distributions = distributions;
+            }
+            if !lengths.is_empty() {
+                config.lengths = lengths;
+            }
+            let results = BenchResults::run(&config)?;
+            match format {
+                BenchFormat::Csv => print!("{}", results.to_csv()),
+                BenchFormat::Markdown => print!("{}", results.to_markdown()),
+            }
+        }
+        Command::Export { input, format, output } => {
+            let output = output.unwrap_or_else(|| {
+                format!("{}_{}.{}", input.algorithm.name(), input.length, format.extension()).into()
+            });
+            let trace = input.run()?.trace();
+            std::fs::write(&output, trace.encode(format))?;
+            println!("wrote {} operations to {}", trace.operations.len(), output.display());
+        }
+        Command::ListAlgorithms => {
+            println!("algorithms:");
+            for method in SortMethod::iter() {
+                println!("  {}", method.name());
+            }
+            println!("distributions:");
+            for distribution in InputDistribution::iter() {
+                println!("  {}", distribution);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,10 +1,21 @@
+mod cli;
 mod sketch;
 
 use async_std::task::block_on;
+use clap::Parser;
+use cli::Cli;
 use sketch::run_app;
 
 fn main() {
-    block_on(async {
-        run_app(0, 0).await;
-    });
-}
\ No newline at end of file
+    match Cli::parse().command {
+        None => block_on(async {
+            run_app(0, 0).await;
+        }),
+        Some(command) => {
+            if let Err(e) = cli::execute(command) {
+                eprintln!("error: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
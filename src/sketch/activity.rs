@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use nannou::color::{Hsv, Srgb};
+use nannou_egui::egui::{self, Color32};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Kinds of access that highlight an index, strongest last
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+pub enum Activity {
+    Read,
+    Compare,
+    Swap,
+    Write,
+}
+
+impl Activity {
+    /// Colour an index is tinted towards while this is its most recent activity. Reads brighten the
+    /// element's own colour instead
+    pub(crate) fn colour(&self) -> (f32, f32, f32) {
+        match self {
+            Activity::Read => (1.0, 1.0, 1.0),
+            Activity::Compare => (0.2, 0.9, 0.3),
+            Activity::Swap => (1.0, 0.75, 0.1),
+            Activity::Write => (0.95, 0.15, 0.15),
+        }
+    }
+}
+
+/// When each index was last read, compared, swapped and written, in ops played. Highlights fade
+/// out linearly over `window` ops
+#[derive(Debug, Clone, Default)]
+pub struct ActivityModel {
+    last: Vec<[Option<usize>; 4]>,
+    /// Time and index of each record that may not have faded yet, oldest first
+    recent: VecDeque<(usize, usize)>,
+    now: usize,
+    window: usize,
+}
+
+impl ActivityModel {
+    pub fn new(length: usize) -> Self {
+        Self { last: vec![[None; 4]; length], recent: VecDeque::new(), now: 0, window: (length / 20).max(1) }
+    }
+    /// Forgets all activity, e.g. on reset or once playback has finished
+    pub fn clear(&mut self) {
+        self.last.iter_mut().for_each(|x| *x = [None; 4]);
+        self.recent.clear();
+        self.now = 0;
+    }
+    /// Moves time on to `now` ops played
+    pub fn advance(&mut self, now: usize) {
+        self.now = now;
+        while self.recent.front().is_some_and(|&(time, _)| now.saturating_sub(time) >= self.window) {
+            self.recent.pop_front();
+        }
+    }
+    pub fn record(&mut self, index: usize, kind: Activity) {
+        if let Some(last) = self.last.get_mut(index) {
+            last[kind as usize] = Some(self.now);
+            self.recent.push_back((self.now, index));
+        }
+    }
+    /// How strongly `index` is highlighted for `kind`, from 1 as it happens to 0 once it has faded
+    pub fn intensity(&self, index: usize, kind: Activity) -> f32 {
+        let Some(time) = self.last.get(index).and_then(|x| x[kind as usize]) else {
+            return 0.0;
+        };
+        let age = self.now.saturating_sub(time);
+        if age >= self.window {
+            return 0.0;
+        }
+        1.0 - age as f32 / self.window as f32
+    }
+    /// Indices that may still be highlighted, possibly more than once
+    pub fn active(&self) -> impl Iterator<Item = usize> + '_ {
+        self.recent.iter().map(|&(_, index)| index)
+    }
+    /// The most recent kind of activity at `index` that hasn't faded, with its intensity
+    pub fn strongest(&self, index: usize) -> Option<(Activity, f32)> {
+        Activity::iter()
+            .map(|kind| (kind, self.intensity(index, kind)))
+            .filter(|&(_, q)| q > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+    /// `base` (an element's resting colour) highlighted by the activity at `index`
+    pub fn tint(&self, index: usize, base: Hsv) -> Srgb {
+        match self.strongest(index) {
+            None => Srgb::from(base),
+            Some((Activity::Read, q)) => {
+                let (s, v) = (base.saturation - 0.2 * q, (base.value + 0.5 * q).min(1.0));
+                Srgb::from(Hsv::new(base.hue, s, v))
+            }
+            Some((kind, q)) => {
+                let base = Srgb::from(base);
+                let (r, g, b) = kind.colour();
+                let mix = |x: f32, y: f32| x + (y - x) * q;
+                Srgb::new(mix(base.red, r), mix(base.green, g), mix(base.blue, b))
+            }
+        }
+    }
+}
+
+/// A line of labels in each activity's colour
+pub fn legend(ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        for kind in Activity::iter() {
+            let (r, g, b) = kind.colour();
+            let colour = Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8);
+            ui.colored_label(colour, format!("{kind:?}"));
+        }
+    });
+}
@@ -1,13 +1,15 @@
 use crate::sketch::*;
 
 
-pub(crate) fn sort(x: &mut List) {
+pub(crate) async fn sort<T: Clone>(x: &mut List<T>) -> Result<(), Stopped> {
     let moves = 1_000_000;
-    let max = moves / x.length;
+    let max = moves / x.length.max(1);
     let mut i = 0;
-    while i < max && !x.is_sorted_visible(){
-        shuffle_step_by_step(x);
+    while i < max && !x.is_sorted_visible().await?{
+        shuffle_step_by_step(x).await?;
+        x.mark_phase().await?;
         i += 1;
     }
+    Ok(())
 }
 
@@ -1,11 +1,14 @@
 use crate::sketch::*;
 
-pub fn sort(ray: &mut List) {
+pub async fn sort<T: Clone>(ray: &mut List<T>) -> Result<(), Stopped> {
     for end in (0..ray.len()).rev() {
         for i in 0..end {
-            if ray.get(i) > ray.get(i + 1) {
-                ray.swap(i, i + 1)
+            let (a, b) = (ray.get(i).await?, ray.get(i + 1).await?);
+            if ray.compare((i, &a), (i + 1, &b)).await?.is_gt() {
+                ray.swap(i, i + 1).await?
             }
         }
+        ray.mark_phase().await?;
     }
+    Ok(())
 }
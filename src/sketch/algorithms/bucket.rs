@@ -1,21 +1,21 @@
 use crate::sketch::*;
 
-pub(crate) fn sort(x: &mut List) {
-    let mut buckets = vec![0; x.len()];
+pub(crate) async fn sort<T: Clone>(x: &mut List<T>) -> Result<(), Stopped> {
+    // Elements are bucketed by level, and kept rather than counted so equal values are written back
+    // in their original order
+    let mut buckets: Vec<Vec<Tagged<T>>> = vec![vec![]; x.len()];
     // for bit in 0..=(x.len().ilog2() as usize) {
     //     sort_by_bit(x, bit);
     // }
     for i in 0..x.len() {
-        let v = x.get(i);
-        buckets[v] += 1;
+        let v = x.get_tagged(i).await?;
+        buckets[v.level].push(v);
     }
+    x.mark_phase().await?;
 
-    let mut i = 0;
-    for (v, q) in buckets.into_iter().enumerate() {
-        for j in i..(q+i) {
-            x.set(j, v)
-        }
-        i += q;
+    for (i, v) in buckets.into_iter().flatten().enumerate() {
+        x.set_tagged(i, v).await?
     }
+    Ok(())
 }
 
@@ -1,49 +1,52 @@
 use crate::sketch::*;
 
-pub(crate) fn sort(x: &mut impl ListPart) {
+pub(crate) async fn sort<T: Clone>(x: &mut impl ListPart<T>) -> Result<(), Stopped> {
     if x.len() <= 1 {
-        return;
+        return Ok(());
     }
     let mid = x.len() / 2;
 
     {
-        let mut left = x.slice(0..mid);
-        sort(&mut left);
+        let mut left = x.slice(0..mid)?;
+        Box::pin(sort(&mut left)).await?;
     }
     {
-        let mut right = x.slice(mid..x.len());
-        sort(&mut right);
+        let mut right = x.slice(mid..x.len())?;
+        Box::pin(sort(&mut right)).await?;
     }
 
-    merge(x, mid);
+    merge(x, mid).await?;
+    x.mark_phase().await
 }
 
-fn merge(x: &mut impl ListPart, mid: usize) {
+async fn merge<T: Clone>(x: &mut impl ListPart<T>, mid: usize) -> Result<(), Stopped> {
     let len = x.len();
     let mut merged = Vec::with_capacity(len);
     let (mut i, mut j) = (0, mid);
 
     while i < mid && j < len {
-        if x.get(i) <= x.get(j) {
-            merged.push(x.get(i));
+        let (a, b) = (x.get(i).await?, x.get(j).await?);
+        if x.compare((i, &a), (j, &b)).await?.is_le() {
+            merged.push(x.get_tagged(i).await?);
             i += 1;
         } else {
-            merged.push(x.get(j));
+            merged.push(x.get_tagged(j).await?);
             j += 1;
         }
     }
 
     while i < mid {
-        merged.push(x.get(i));
+        merged.push(x.get_tagged(i).await?);
         i += 1;
     }
 
     while j < len {
-        merged.push(x.get(j));
+        merged.push(x.get_tagged(j).await?);
         j += 1;
     }
 
     for (k, value) in merged.into_iter().enumerate() {
-        x.set(k, value);
+        x.set_tagged(k, value).await?;
     }
+    Ok(())
 }
@@ -5,3 +5,6 @@ pub mod radix;
 pub mod selection;
 pub mod bucket;
 pub mod bogo;
+
+#[cfg(test)]
+mod tests;
@@ -1,13 +1,13 @@
 use crate::sketch::*;
 
-pub fn sort(ray: &mut impl ListPart) {
+pub async fn sort<T: Clone>(ray: &mut impl ListPart<T>) -> Result<(), Stopped> {
     // Base case: if the list is empty or has one element, it's already sorted.
     if ray.len() <= 1 {
-        return;
+        return Ok(());
     }
 
     // Use the first element as the pivot.
-    let pivot = ray.get(0);
+    let pivot = ray.get(0).await?;
     let len = ray.len();
 
     // i marks the start of the region for elements greater than or equal to pivot.
@@ -15,18 +15,20 @@ pub fn sort(ray: &mut impl ListPart) {
 
     // Partition the list into two parts: < pivot and >= pivot.
     for j in 1..len {
-        if ray.get(j) < pivot {
-            ray.swap(i, j);
+        let v = ray.get(j).await?;
+        if ray.compare((j, &v), (0, &pivot)).await?.is_lt() {
+            ray.swap(i, j).await?;
             i += 1;
         }
     }
 
     // Place the pivot into its correct position.
-    ray.swap(0, i - 1);
+    ray.swap(0, i - 1).await?;
+    ray.mark_phase().await?;
 
     // Recursively sort the left and right partitions.
-    let mut left = ray.slice(0..(i - 1));
-    sort(&mut left);
-    let mut right = ray.slice(i..len);
-    sort(&mut right);
+    let mut left = ray.slice(0..(i - 1))?;
+    Box::pin(sort(&mut left)).await?;
+    let mut right = ray.slice(i..len)?;
+    Box::pin(sort(&mut right)).await
 }
@@ -1,29 +1,36 @@
 use crate::sketch::*;
 
-pub(crate) fn sort(x: &mut List, base: usize) {
+/// Sorts by the digits of each element's level
+pub(crate) async fn sort<T: Clone>(x: &mut List<T>, base: usize) -> Result<(), Stopped> {
     for n in 0..((x.len() as f64).log(base as f64).ceil() as usize) {
-        sort_by_base_n(x, base, n);
+        sort_by_base_n(x, base, n).await?;
+        x.mark_phase().await?;
     }
+    Ok(())
 }
 
-pub  fn sort_by_base_n(x: &mut List, base: usize, n: usize) {
-    let mut buckets: Vec<Vec<usize>> = vec![vec![]; base];
+pub  async fn sort_by_base_n<T: Clone>(x: &mut List<T>, base: usize, n: usize) -> Result<(), Stopped> {
+    let mut buckets: Vec<Vec<Tagged<T>>> = vec![vec![]; base];
     let bp = base.pow(n as u32);
     for i in 0..x.len() {
-        let v = x.get(i);
-        let rem = (v / bp) % base;
+        let v = x.get_tagged(i).await?;
+        let rem = (v.level / bp) % base;
         buckets[rem].push(v)
     }
-    buckets.into_iter().flatten().enumerate().for_each(|(i, v)| x.set(i, v));
+    for (i, v) in buckets.into_iter().flatten().enumerate() {
+        x.set_tagged(i, v).await?;
+    }
+    Ok(())
 }
 
-pub fn sort_by_bit(x: &mut List, bit: usize) {
+#[allow(dead_code)]
+pub async fn sort_by_bit<T: Clone>(x: &mut List<T>, bit: usize) -> Result<(), Stopped> {
     let mut v1 = vec![];
     let mut v2 = vec![];
 
     for i in 0..x.len() {
-        let r = x.get(i);
-        if !n_th_bit(r, bit) {
+        let r = x.get_tagged(i).await?;
+        if !n_th_bit(r.level, bit) {
             v1.push(r)
         } else {
             v2.push(r)
@@ -31,14 +38,16 @@ pub fn sort_by_bit(x: &mut List, bit: usize) {
     }
     let len = v1.len();
     for (i, value) in v1.into_iter().enumerate() {
-        x.set(i, value);
+        x.set_tagged(i, value).await?;
     }
     for (i, value) in v2.into_iter().enumerate() {
-        x.set(i + len, value);
+        x.set_tagged(i + len, value).await?;
     }
     // *x = a;
+    Ok(())
 }
 
+#[allow(dead_code)]
 fn n_th_bit(x: usize, bit: usize) -> bool {
     (x >> bit) & 1 == 1
 }
\ No newline at end of file
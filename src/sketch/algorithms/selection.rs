@@ -1,14 +1,17 @@
 use crate::sketch::*;
 
-pub fn sort(ray: &mut List) {
-    for i in 0..(ray.len() - 1) {
-        let min_func = |i: usize, ray: &mut List| (i, ray.get(i));
-        let mut min = min_func(i, ray);
+pub async fn sort<T: Clone>(ray: &mut List<T>) -> Result<(), Stopped> {
+    for i in 0..ray.len().saturating_sub(1) {
+        let min_func = async |i: usize, ray: &mut List<T>| ray.get(i).await.map(|v| (i, v));
+        let mut min = min_func(i, ray).await?;
         for j in (i + 1)..ray.len() {
-            if ray.get(j) < min.1 {
-                min = min_func(j, ray);
+            let v = ray.get(j).await?;
+            if ray.compare((j, &v), (min.0, &min.1)).await?.is_lt() {
+                min = min_func(j, ray).await?;
             }
         }
-        ray.swap(i, min.0);
+        ray.swap(i, min.0).await?;
+        ray.mark_phase().await?;
     }
+    Ok(())
 }
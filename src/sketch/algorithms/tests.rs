@@ -0,0 +1,143 @@
+use crate::sketch::distribution::InputDistribution;
+use crate::sketch::headless::SortRun;
+use crate::sketch::list::{List, ListPart, Operation, Stopped};
+use crate::sketch::methods::SortMethod;
+use crate::sketch::player::apply_op;
+use crate::sketch::trace::{block_on, TraceEvent, TraceStream};
+use strum::IntoEnumIterator;
+
+const LENGTHS: [usize; 12] = [0, 1, 2, 3, 5, 7, 8, 16, 31, 100, 257, 1000];
+
+/// Bogo sort gives up after a fixed number of shuffles, so it's only expected to finish on tiny inputs
+const BOGO_MAX_LENGTH: usize = 5;
+
+fn check(method: SortMethod, distribution: InputDistribution, length: usize, seed: u64) {
+    let case = format!("{method:?} on {distribution} input of length {length} (seed {seed})");
+    let run = SortRun::new(method, distribution, length, seed).unwrap_or_else(|e| panic!("{case}: {e:#}"));
+
+    assert!(run.list.is_sorted(), "{case}: result isn't sorted");
+    let mut expected = run.input.clone();
+    expected.sort();
+    let mut result: Vec<usize> = run.list.iter().copied().collect();
+    result.sort();
+    assert_eq!(result, expected, "{case}: result isn't a permutation of the input");
+
+    let mut replayed = run.input.clone();
+    for (n, op) in run.list.record_of_operations.iter().enumerate() {
+        let in_bounds = match op {
+            Operation::Get(i) | Operation::Set(i, _) => i < length,
+            Operation::Swap(i, j) => i < length && j < length,
+        };
+        assert!(in_bounds, "{case}: op {n} ({op:?}) is out of bounds");
+        apply_op(&mut replayed, op);
+    }
+    let sorted: Vec<usize> = run.list.iter().copied().collect();
+    assert_eq!(replayed, sorted, "{case}: replaying the trace doesn't reproduce the result");
+
+    if method.is_stable() {
+        assert!(run.list.is_stable(), "{case}: equal values were reordered by a stable sort");
+    }
+
+    let ops = run.list.record_of_operations.len();
+    assert!(
+        run.list.phase_boundaries.iter().all(|&p| p <= ops) && run.list.phase_boundaries.is_sorted(),
+        "{case}: phase boundaries aren't ascending op indices"
+    );
+}
+
+#[test]
+fn every_algorithm_sorts_and_records_a_valid_trace() {
+    for method in SortMethod::iter() {
+        for distribution in InputDistribution::iter() {
+            for length in LENGTHS {
+                if method == SortMethod::Bogo && length > BOGO_MAX_LENGTH {
+                    continue;
+                }
+                for seed in 0..3 {
+                    check(method, distribution, length, seed);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn unstable_algorithms_reorder_equal_values() {
+    for method in SortMethod::iter().filter(|m| !m.is_stable()) {
+        let reordered = if method == SortMethod::Bogo {
+            // Keeps both pairs in order one time in four
+            (0..20).any(|_| {
+                let mut list = List::new(vec![1, 1, 0, 0], 4);
+                block_on(method.run(&mut list)).unwrap();
+                !list.is_stable()
+            })
+        } else {
+            (0..3).any(|seed| !SortRun::new(method, InputDistribution::FewUnique, 100, seed).unwrap().list.is_stable())
+        };
+        assert!(reordered, "{method:?} never reordered equal values, is it really unstable?");
+    }
+}
+
+fn check_order<T: Clone + std::fmt::Debug>(method: SortMethod, input: Vec<T>, order: fn(&T, &T) -> std::cmp::Ordering) {
+    let mut list = List::with_order(input, order);
+    let mut replayed = list.levels().to_vec();
+    block_on(method.run(&mut list)).unwrap();
+    assert!(list.is_sorted(), "{method:?}: {:?} isn't sorted", list.iter().collect::<Vec<_>>());
+    for op in list.record_of_operations.iter() {
+        apply_op(&mut replayed, op);
+    }
+    assert_eq!(replayed, list.levels(), "{method:?}: replaying the trace doesn't reproduce the levels");
+    assert!(replayed.is_sorted(), "{method:?}: levels aren't in the same order as the elements");
+}
+
+#[test]
+fn every_algorithm_sorts_other_element_types() {
+    for method in SortMethod::iter().filter(|&m| m != SortMethod::Bogo) {
+        let floats = (0..50).map(|i| ((i * 37) % 50) as f64 / 7.0 - 3.0).collect();
+        check_order(method, floats, f64::total_cmp);
+        let words = ["pear", "fig", "apple", "fig", "kiwi", "date", "", "plum"].map(String::from).to_vec();
+        check_order(method, words, String::cmp);
+        let records: Vec<(u8, char)> = "sortingrecords".chars().enumerate().map(|(i, c)| ((i % 3) as u8, c)).collect();
+        check_order(method, records, |a, b| a.0.cmp(&b.0));
+    }
+}
+
+#[test]
+fn out_of_bounds_accesses_are_reported() {
+    fn error(access: impl AsyncFnOnce(&mut List) -> Result<(), Stopped>) -> String {
+        let mut list = List::new((0..8).collect(), 8);
+        match block_on(access(&mut list)) {
+            Err(Stopped::OutOfBounds(e)) => e.to_string(),
+            other => panic!("access should be out of bounds, got {other:?}"),
+        }
+    }
+    assert_eq!(error(async |l| l.get(8).await.map(drop)), "op 0: get(8) is outside the list (valid indices 0..8)");
+    assert_eq!(error(async |l| l.swap(1, 9).await), "op 0: swap(1, 9) is outside the list (valid indices 0..8)");
+    assert_eq!(error(async |l| l.slice(4..9).map(drop)), "op 0: slice(4..9) is outside the list (valid indices 0..8)");
+    assert_eq!(
+        error(async |l| { l.get(0).await?; l.slice(2..5)?.get(3).await.map(drop) }),
+        "op 1: get(3) is outside slice 2..5 (valid indices 0..3)"
+    );
+    assert_eq!(
+        error(async |l| l.slice(2..6)?.slice(1..4)?.swap(0, 3).await),
+        "op 0: swap(0, 3) is outside slice 3..6 (valid indices 0..3)"
+    );
+    assert_eq!(
+        error(async |l| l.slice(2..6)?.slice(3..5).map(drop)),
+        "op 0: slice(3..5) is outside slice 2..6 (valid indices 0..4)"
+    );
+}
+
+#[test]
+fn compares_are_traced_to_the_indices_compared() {
+    let compared = |method: SortMethod, input: Vec<usize>| {
+        let length = input.len();
+        let mut stream = TraceStream::new(List::new(input, length), method, false);
+        let events: Vec<_> = std::iter::from_fn(|| stream.next()).collect();
+        events.into_iter().filter_map(|e| if let TraceEvent::Compare(i) = e { Some(i) } else { None }).collect::<Vec<_>>()
+    };
+    // Each adjacent pair
+    assert_eq!(compared(SortMethod::Bubble, vec![2, 0, 1]), [0, 1, 1, 2, 0, 1]);
+    // Each element against the pivot, which was read before all of them
+    assert_eq!(compared(SortMethod::Quick, vec![1, 2, 0]), [1, 0, 2, 0]);
+}
@@ -1,13 +1,63 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Sample, SampleFormat, StreamConfig};
-use std::sync::{Arc, Mutex};
+use cpal::{SampleFormat, StreamConfig};
+use std::f64::consts::{FRAC_PI_4, TAU};
+use std::fmt::Formatter;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use strum_macros::EnumIter;
 
 #[derive(Clone, Copy, Debug)]
 pub struct AudioModel {
     pub phase: f64,
     pub hz: f64,
     pub volume: f64,
+    /// Stereo position of the current note, -1.0 is hard left and 1.0 is hard right
+    pub pan: f64,
+    pub pan_mode: PanMode,
+}
+
+/// How the index touched by an operation is mapped to a stereo position
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+pub enum PanMode {
+    Centre,
+    Linear,
+    Circular,
+}
+
+impl PanMode {
+    /// `prop` is the touched index as a proportion of the array length
+    pub fn pan(&self, prop: f64) -> f64 {
+        match self {
+            PanMode::Centre => 0.0,
+            // Left edge of the array is hard left, right edge is hard right
+            PanMode::Linear => (prop * 2.0 - 1.0).clamp(-1.0, 1.0),
+            // Matches the angle used by the circular renderers, so the sound follows the x position
+            PanMode::Circular => (prop * TAU).cos(),
+        }
+    }
+}
+
+impl std::fmt::Display for PanMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Equal power gain for output channel `ch`, channels beyond the first two stay centred. A hard
+/// panned channel is at full volume, so a centred note is 3 dB quieter in each channel
+fn channel_gain(pan: f64, ch: usize, channels: usize) -> f32 {
+    if channels < 2 {
+        return 1.0;
+    }
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    let gain = match ch {
+        0 => angle.cos(),
+        1 => angle.sin(),
+        _ => FRAC_PI_4.cos(),
+    };
+    gain as f32
 }
 
 impl Default for AudioModel {
@@ -18,12 +68,62 @@ impl Default for AudioModel {
             0.2
         };
 
-        Self { phase: 0.0, hz: 440.0, volume }
+        Self { phase: 0.0, hz: 440.0, volume, pan: 0.0, pan_mode: PanMode::Linear }
+    }
+}
+
+impl AudioModel {
+    /// Advances the oscillator by one frame and returns the mono sample, used by both the live
+    /// stream and the offline renderer
+    pub fn next_sample(&mut self, sample_rate: f64) -> f32 {
+        let t = self.phase % 1.0;
+        let triangle = true;
+        let sample = if triangle { 4.0 * (t - 0.5).abs() - 1.0 } else { (2.0 * std::f64::consts::PI * self.phase).sin() } as f32;
+        self.phase += self.hz / sample_rate;
+        if self.phase >= 1.0 { self.phase -= 1.0; }
+        (sample * self.volume as f32).clamp(-1.0, 1.0)
+    }
+
+    /// Applies the current pan to a mono sample for output channel `ch`
+    pub fn channel_sample(&self, sample: f32, ch: usize, channels: usize) -> f32 {
+        (sample * channel_gain(self.pan, ch, channels)).clamp(-1.0, 1.0)
+    }
+}
+
+/// Parameters the UI thread publishes to the audio callback. Each field is an `f64` stored as
+/// its bits, so neither side ever blocks. The oscillator phase lives only in the callback.
+#[derive(Debug, Default)]
+pub struct AudioParams {
+    hz: AtomicU64,
+    volume: AtomicU64,
+    pan: AtomicU64,
+}
+
+impl AudioParams {
+    pub fn new(model: &AudioModel) -> Self {
+        let params = Self::default();
+        params.store(model);
+        params
+    }
+
+    pub fn store(&self, model: &AudioModel) {
+        self.hz.store(model.hz.to_bits(), Ordering::Relaxed);
+        self.volume.store(model.volume.to_bits(), Ordering::Relaxed);
+        self.pan.store(model.pan.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Copies the published parameters into `model`, leaving its phase untouched
+    pub fn load(&self, model: &mut AudioModel) {
+        model.hz = f64::from_bits(self.hz.load(Ordering::Relaxed));
+        model.volume = f64::from_bits(self.volume.load(Ordering::Relaxed));
+        model.pan = f64::from_bits(self.pan.load(Ordering::Relaxed));
     }
 }
 
 pub struct AudioHandle {
-    pub model: Arc<Mutex<AudioModel>>, // shared state for callback updates
+    /// UI side copy of the model, `send` edits this and then publishes it to `params`
+    model: Cell<AudioModel>,
+    pub params: Arc<AudioParams>, // shared state for callback updates
     pub stream: cpal::Stream,
 }
 
@@ -31,7 +131,10 @@ impl AudioHandle {
     pub fn play(&self) -> Result<()> { self.stream.play()?; Ok(()) }
     pub fn pause(&self) -> Result<()> { self.stream.pause()?; Ok(()) }
     pub fn send<F: FnOnce(&mut AudioModel)>(&self, f: F) -> Result<()> {
-        if let Ok(mut m) = self.model.lock() { f(&mut *m); }
+        let mut m = self.model.get();
+        f(&mut m);
+        self.model.set(m);
+        self.params.store(&m);
         Ok(())
     }
 }
@@ -46,99 +149,95 @@ pub fn start_audio(initial: AudioModel) -> Result<AudioHandle> {
 
     let sample_format = device.default_output_config()?.sample_format();
 
-    let shared = Arc::new(Mutex::new(initial));
-    let shared_cb = shared.clone();
+    let params = Arc::new(AudioParams::new(&initial));
 
     let stream = match sample_format {
-        SampleFormat::F32 => build_stream_f32(&device, &config, shared_cb)?,
-        SampleFormat::I16 => build_stream_i16(&device, &config, shared_cb)?,
-        SampleFormat::U16 => build_stream_u16(&device, &config, shared_cb)?,
-        _ => build_stream_f32(&device, &config, shared_cb)?,
+        SampleFormat::F32 => build_stream(&device, &config, initial, params.clone(), |s| s)?,
+        SampleFormat::I16 => build_stream(&device, &config, initial, params.clone(), |s| (s * i16::MAX as f32) as i16)?,
+        // Map [-1.0,1.0] -> [0,u16::MAX]
+        SampleFormat::U16 => build_stream(&device, &config, initial, params.clone(), |s| ((s * 0.5 + 0.5) * u16::MAX as f32) as u16)?,
     };
 
-    Ok(AudioHandle { model: shared, stream })
+    Ok(AudioHandle { model: Cell::new(initial), params, stream })
 }
 
-fn build_stream_f32(device: &cpal::Device, config: &StreamConfig, shared: Arc<Mutex<AudioModel>>) -> Result<cpal::Stream> {
-    let channels = config.channels as usize;
-    let sample_rate = config.sample_rate.0 as f64;
-    let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
-
-    let stream = device.build_output_stream(
-        config,
-        move |data: &mut [f32], _| {
-            let mut guard = shared.lock().ok();
-            let (hz, volume, phase_start) = if let Some(ref m) = guard { (m.hz, m.volume, m.phase) } else { (440.0, 0.0, 0.0) };
-            let mut phase = phase_start;
-            for frame in data.chunks_mut(channels) {
-                let t = phase % 1.0;
-                let triangle = true;
-                let sample = if triangle { 4.0 * (t - 0.5).abs() - 1.0 } else { (2.0 * std::f64::consts::PI * phase).sin() } as f32;
-                phase += hz / sample_rate;
-                if phase >= 1.0 { phase -= 1.0; }
-                let s = (sample * volume as f32).clamp(-1.0, 1.0);
-                for ch in frame.iter_mut() { *ch = s; }
-            }
-            if let Some(ref mut m) = guard { m.phase = phase; }
-        },
-        err_fn,
-    )?;
-    Ok(stream)
+/// Fills one callback buffer, picking up whatever parameters were last published
+fn render_block<T>(data: &mut [T], channels: usize, sample_rate: f64, model: &mut AudioModel, params: &AudioParams, convert: fn(f32) -> T) {
+    params.load(model);
+    for frame in data.chunks_mut(channels) {
+        let s = model.next_sample(sample_rate);
+        for (c, ch) in frame.iter_mut().enumerate() { *ch = convert(model.channel_sample(s, c, channels)); }
+    }
 }
 
-fn build_stream_i16(device: &cpal::Device, config: &StreamConfig, shared: Arc<Mutex<AudioModel>>) -> Result<cpal::Stream> {
+fn build_stream<T: cpal::Sample + 'static>(device: &cpal::Device, config: &StreamConfig, initial: AudioModel, params: Arc<AudioParams>, convert: fn(f32) -> T) -> Result<cpal::Stream> {
     let channels = config.channels as usize;
     let sample_rate = config.sample_rate.0 as f64;
     let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
 
+    // Owned by the callback so the phase carries over between buffers
+    let mut model = initial;
     let stream = device.build_output_stream(
         config,
-        move |data: &mut [i16], _| {
-            let mut guard = shared.lock().ok();
-            let (hz, volume, phase_start) = if let Some(ref m) = guard { (m.hz, m.volume, m.phase) } else { (440.0, 0.0, 0.0) };
-            let mut phase = phase_start;
-            for frame in data.chunks_mut(channels) {
-                let t = phase % 1.0;
-                let triangle = true;
-                let sample = if triangle { 4.0 * (t - 0.5).abs() - 1.0 } else { (2.0 * std::f64::consts::PI * phase).sin() } as f32;
-                phase += hz / sample_rate;
-                if phase >= 1.0 { phase -= 1.0; }
-                let s = (sample * volume as f32).clamp(-1.0, 1.0);
-                let v = (s * i16::MAX as f32) as i16;
-                for ch in frame.iter_mut() { *ch = v; }
-            }
-            if let Some(ref mut m) = guard { m.phase = phase; }
-        },
+        move |data: &mut [T], _| render_block(data, channels, sample_rate, &mut model, &params, convert),
         err_fn,
     )?;
     Ok(stream)
 }
 
-fn build_stream_u16(device: &cpal::Device, config: &StreamConfig, shared: Arc<Mutex<AudioModel>>) -> Result<cpal::Stream> {
-    let channels = config.channels as usize;
-    let sample_rate = config.sample_rate.0 as f64;
-    let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
 
-    let stream = device.build_output_stream(
-        config,
-        move |data: &mut [u16], _| {
-            let mut guard = shared.lock().ok();
-            let (hz, volume, phase_start) = if let Some(ref m) = guard { (m.hz, m.volume, m.phase) } else { (440.0, 0.0, 0.0) };
-            let mut phase = phase_start;
-            for frame in data.chunks_mut(channels) {
-                let t = phase % 1.0;
-                let triangle = true;
-                let sample = if triangle { 4.0 * (t - 0.5).abs() - 1.0 } else { (2.0 * std::f64::consts::PI * phase).sin() } as f32;
-                phase += hz / sample_rate;
-                if phase >= 1.0 { phase -= 1.0; }
-                let s = (sample * volume as f32).clamp(-1.0, 1.0);
-                // Map [-1.0,1.0] -> [0,u16::MAX]
-                let v = ((s * 0.5 + 0.5) * u16::MAX as f32) as u16;
-                for ch in frame.iter_mut() { *ch = v; }
+    #[test]
+    fn panning_never_boosts_a_channel() {
+        for pan in [-1.0, -0.5, 0.0, 0.5, 1.0] {
+            for ch in 0..4 {
+                assert!(channel_gain(pan, ch, 4) <= 1.0);
             }
-            if let Some(ref mut m) = guard { m.phase = phase; }
-        },
-        err_fn,
-    )?;
-    Ok(stream)
+        }
+        assert_eq!(channel_gain(-1.0, 0, 2), 1.0);
+        assert_eq!(channel_gain(0.0, 0, 2), channel_gain(0.0, 1, 2));
+    }
+
+    #[test]
+    fn concurrent_updates_while_rendering() {
+        let initial = AudioModel { volume: 0.5, ..Default::default() };
+        let params = Arc::new(AudioParams::new(&initial));
+
+        let writer = {
+            let params = params.clone();
+            thread::spawn(move || {
+                let mut model = initial;
+                for n in 0..100_000 {
+                    model.hz = 120.0 + (n % 1000) as f64;
+                    model.pan = ((n % 200) as f64 / 100.0) - 1.0;
+                    params.store(&model);
+                }
+                model.hz = 1212.0;
+                model.pan = 1.0;
+                params.store(&model);
+            })
+        };
+
+        let mut model = initial;
+        let mut data = vec![0.0_f32; 512 * 2];
+        let mut blocks = 0;
+        while blocks < 100 || !writer.is_finished() {
+            render_block(&mut data, 2, 44100.0, &mut model, &params, |s| s);
+            assert!(data.iter().all(|s| s.is_finite() && (-1.0..=1.0).contains(s)));
+            assert!((120.0..=1212.0).contains(&model.hz));
+            assert!((-1.0..=1.0).contains(&model.pan));
+            blocks += 1;
+        }
+        writer.join().unwrap();
+
+        render_block(&mut data, 2, 44100.0, &mut model, &params, |s| s);
+        assert_eq!(model.hz, 1212.0);
+        assert_eq!(model.pan, 1.0);
+        // Hard right pan silences the left channel
+        assert!(data.chunks(2).all(|f| f[0].abs() < 1e-6));
+        assert!(data.chunks(2).any(|f| f[1] != 0.0));
+    }
 }
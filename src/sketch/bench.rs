@@ -0,0 +1,261 @@
+use crate::sketch::distribution::InputDistribution;
+use crate::sketch::headless::{OpCounts, SortRun};
+use crate::sketch::methods::SortMethod;
+use std::fmt::{Formatter, Write};
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
+
+/// What to run: every combination of algorithm, distribution and length
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    pub algorithms: Vec<SortMethod>,
+    pub distributions: Vec<InputDistribution>,
+    pub lengths: Vec<usize>,
+    pub seed: u64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            // Bogo sort gives up long before n = 1024, so its counts say nothing about growth
+            algorithms: SortMethod::iter().filter(|&m| m != SortMethod::Bogo).collect(),
+            distributions: InputDistribution::iter().collect(),
+            lengths: (4..=10).map(|p| 1 << p).collect(),
+            seed: 0,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Every (algorithm, distribution, length) to run, in order
+    pub fn cases(&self) -> Vec<(SortMethod, InputDistribution, usize)> {
+        let mut cases = vec![];
+        for &method in &self.algorithms {
+            for &distribution in &self.distributions {
+                for &length in &self.lengths {
+                    cases.push((method, distribution, length));
+                }
+            }
+        }
+        cases
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchRow {
+    pub method: SortMethod,
+    pub distribution: InputDistribution,
+    pub length: usize,
+    pub counts: OpCounts,
+    pub sorted: bool,
+    pub millis: f64,
+}
+
+impl BenchRow {
+    /// Runs one case of a `BenchConfig`
+    pub fn run(method: SortMethod, distribution: InputDistribution, length: usize, seed: u64) -> anyhow::Result<Self> {
+        let start = now_millis();
+        let run = SortRun::new(method, distribution, length, seed)?;
+        let millis = now_millis() - start;
+        Ok(Self { method, distribution, length, counts: run.counts(), sorted: run.list.is_sorted(), millis })
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Metric {
+    Comparisons,
+    Reads,
+    Writes,
+    Swaps,
+    Total,
+}
+
+impl Metric {
+    pub fn of(&self, counts: &OpCounts) -> usize {
+        match self {
+            Metric::Comparisons => counts.comparisons,
+            Metric::Reads => counts.reads,
+            Metric::Writes => counts.writes,
+            Metric::Swaps => counts.swaps,
+            Metric::Total => counts.total(),
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Growth rates a metric is fitted against
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+pub enum GrowthModel {
+    Linear,
+    NLogN,
+    Quadratic,
+}
+
+impl GrowthModel {
+    pub fn f(&self, n: f64) -> f64 {
+        match self {
+            GrowthModel::Linear => n,
+            GrowthModel::NLogN => n * n.log2().max(1.0),
+            GrowthModel::Quadratic => n * n,
+        }
+    }
+}
+
+impl std::fmt::Display for GrowthModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            GrowthModel::Linear => "n",
+            GrowthModel::NLogN => "n log n",
+            GrowthModel::Quadratic => "n²",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// `y ≈ coefficient * model(n)`, with `error` the RMS of the relative residuals
+#[derive(Debug, Copy, Clone)]
+pub struct Fit {
+    pub model: GrowthModel,
+    pub coefficient: f64,
+    pub error: f64,
+}
+
+impl std::fmt::Display for Fit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} {} (±{:.0}%)", self.coefficient, self.model, self.error * 100.0)
+    }
+}
+
+impl Fit {
+    /// Least squares fit of `y = c * model(n)` on relative error, so small and large n count equally
+    pub fn new(model: GrowthModel, points: &[(usize, usize)]) -> Option<Self> {
+        let points: Vec<(f64, f64)> = points
+            .iter()
+            .filter(|(n, y)| *n > 1 && *y > 0)
+            .map(|&(n, y)| (model.f(n as f64), y as f64))
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+        let coefficient = points.iter().map(|(f, y)| f / y).sum::<f64>()
+            / points.iter().map(|(f, y)| (f / y).powi(2)).sum::<f64>();
+        let error = (points.iter().map(|(f, y)| ((y - coefficient * f) / y).powi(2)).sum::<f64>()
+            / points.len() as f64)
+            .sqrt();
+        Some(Self { model, coefficient, error })
+    }
+
+    /// The model that fits best, `None` if the metric is zero or there are too few lengths
+    pub fn best(points: &[(usize, usize)]) -> Option<Self> {
+        GrowthModel::iter()
+            .filter_map(|model| Fit::new(model, points))
+            .min_by(|a, b| a.error.total_cmp(&b.error))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BenchResults {
+    pub rows: Vec<BenchRow>,
+}
+
+impl BenchResults {
+    /// Runs every combination in `config`, stopping at the first algorithm that fails
+    // Only the sort_bin command line runs a whole benchmark at once, the window runs it in steps
+    #[allow(dead_code)]
+    pub fn run(config: &BenchConfig) -> anyhow::Result<Self> {
+        let rows = config.cases().into_iter().map(|(method, distribution, length)| BenchRow::run(method, distribution, length, config.seed));
+        Ok(Self { rows: rows.collect::<anyhow::Result<_>>()? })
+    }
+
+    /// (length, metric) for one algorithm on one distribution, ordered by length. Runs that gave up
+    /// before sorting are left out, since their counts don't follow any growth model
+    pub fn series(&self, method: SortMethod, distribution: InputDistribution, metric: Metric) -> Vec<(usize, usize)> {
+        self.rows
+            .iter()
+            .filter(|r| r.method == method && r.distribution == distribution && r.sorted)
+            .map(|r| (r.length, metric.of(&r.counts)))
+            .collect()
+    }
+
+    /// Every (algorithm, distribution) pair that was run, in run order
+    fn groups(&self) -> Vec<(SortMethod, InputDistribution)> {
+        let mut groups = vec![];
+        for r in &self.rows {
+            if !groups.contains(&(r.method, r.distribution)) {
+                groups.push((r.method, r.distribution));
+            }
+        }
+        groups
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("algorithm,distribution,length,comparisons,reads,writes,swaps,total,sorted,ms\n");
+        for r in &self.rows {
+            let c = &r.counts;
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{:.3}",
+                r.method.name(), r.distribution, r.length, c.comparisons, c.reads, c.writes, c.swaps,
+                c.total(), r.sorted, r.millis,
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// The counts table followed by the best growth model for each metric
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("| algorithm | distribution | n | comparisons | reads | writes | swaps | total | sorted |\n");
+        md.push_str("| --- | --- | ---: | ---: | ---: | ---: | ---: | ---: | --- |\n");
+        for r in &self.rows {
+            let c = &r.counts;
+            writeln!(
+                md,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                r.method.name(), r.distribution, r.length, c.comparisons, c.reads, c.writes, c.swaps, c.total(),
+                if r.sorted { "yes" } else { "**no**, left out of the fits" },
+            )
+            .unwrap();
+        }
+
+        md.push_str("\n| algorithm | distribution |");
+        for metric in Metric::iter() {
+            write!(md, " {} |", metric).unwrap();
+        }
+        md.push_str("\n| --- | --- |");
+        md.push_str(&" --- |".repeat(Metric::iter().count()));
+        md.push('\n');
+        for (method, distribution) in self.groups() {
+            write!(md, "| {} | {} |", method.name(), distribution).unwrap();
+            for metric in Metric::iter() {
+                match Fit::best(&self.series(method, distribution, metric)) {
+                    Some(fit) => write!(md, " {} |", fit).unwrap(),
+                    None => md.push_str(" - |"),
+                }
+            }
+            md.push('\n');
+        }
+        md
+    }
+}
+
+/// Milliseconds from a monotonic clock, `Instant` isn't available on wasm
+fn now_millis() -> f64 {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        js_sys::Date::now()
+    }
+}
@@ -0,0 +1,181 @@
+use crate::sketch::bench::{BenchConfig, BenchResults, BenchRow, Fit, Metric};
+use crate::sketch::distribution::InputDistribution;
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use nannou_egui::egui::{self, ComboBox, ProgressBar, Window};
+use strum::IntoEnumIterator;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+#[cfg(target_family = "wasm")]
+use crate::sketch::methods::SortMethod;
+
+/// State of the benchmark window: results of the last run and what is being plotted
+pub struct BenchView {
+    pub open: bool,
+    results: Option<BenchResults>,
+    /// The run in progress, if there is one
+    running: Option<Running>,
+    /// Why the last run failed, if it did
+    error: Option<String>,
+    metric: Metric,
+    distribution: InputDistribution,
+}
+
+impl Default for BenchView {
+    fn default() -> Self {
+        Self {
+            open: false,
+            results: None,
+            running: None,
+            error: None,
+            metric: Metric::Comparisons,
+            distribution: InputDistribution::Shuffled,
+        }
+    }
+}
+
+/// A benchmark run that finishes over several frames. Natively the cases run on a worker thread,
+/// on the web (which has no threads) one case runs each frame
+struct Running {
+    rows: Vec<BenchRow>,
+    total: usize,
+    #[cfg(not(target_family = "wasm"))]
+    receiver: Receiver<anyhow::Result<BenchRow>>,
+    #[cfg(target_family = "wasm")]
+    cases: std::vec::IntoIter<(SortMethod, InputDistribution, usize)>,
+    #[cfg(target_family = "wasm")]
+    seed: u64,
+}
+
+impl Running {
+    fn start(config: BenchConfig) -> Self {
+        let cases = config.cases();
+        let total = cases.len();
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                for (method, distribution, length) in cases {
+                    let row = BenchRow::run(method, distribution, length, config.seed);
+                    let failed = row.is_err();
+                    // Stop once the window has stopped listening or a case fails
+                    if sender.send(row).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+            Self { rows: vec![], total, receiver }
+        }
+        #[cfg(target_family = "wasm")]
+        Self { rows: vec![], total, cases: cases.into_iter(), seed: config.seed }
+    }
+
+    /// Collects finished cases, returning the results once every case has run or one has failed
+    fn poll(&mut self) -> Option<anyhow::Result<BenchResults>> {
+        #[cfg(not(target_family = "wasm"))]
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Ok(row)) => self.rows.push(row),
+                Ok(Err(e)) => return Some(Err(e)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) if self.rows.len() < self.total => {
+                    return Some(Err(anyhow::anyhow!("the benchmark stopped after {} of {} runs", self.rows.len(), self.total)));
+                }
+                Err(TryRecvError::Disconnected) => return Some(Ok(BenchResults { rows: std::mem::take(&mut self.rows) })),
+            }
+        }
+        #[cfg(target_family = "wasm")]
+        {
+            let Some((method, distribution, length)) = self.cases.next() else {
+                return Some(Ok(BenchResults { rows: std::mem::take(&mut self.rows) }));
+            };
+            match BenchRow::run(method, distribution, length, self.seed) {
+                Ok(row) => self.rows.push(row),
+                Err(e) => return Some(Err(e)),
+            }
+            None
+        }
+    }
+}
+
+impl BenchView {
+    pub fn show(&mut self, ctx: &egui::Context) {
+        match self.running.as_mut().and_then(Running::poll) {
+            Some(Ok(results)) => (self.results, self.error, self.running) = (Some(results), None, None),
+            Some(Err(e)) => (self.results, self.error, self.running) = (None, Some(format!("{e:#}")), None),
+            // Keep drawing frames so progress shows without waiting for input
+            None if self.running.is_some() => ctx.request_repaint(),
+            None => {}
+        }
+        let mut open = self.open;
+        Window::new("Benchmark").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.running.is_none(), egui::Button::new("Run")).clicked() {
+                    self.running = Some(Running::start(BenchConfig::default()));
+                }
+                ComboBox::from_label("Metric")
+                    .selected_text(format!("{}", self.metric))
+                    .show_ui(ui, |ui| {
+                        for option in Metric::iter() {
+                            ui.selectable_value(&mut self.metric, option, format!("{option}"));
+                        }
+                    });
+                ComboBox::from_label("Input")
+                    .selected_text(format!("{}", self.distribution))
+                    .show_ui(ui, |ui| {
+                        for option in InputDistribution::iter() {
+                            ui.selectable_value(&mut self.distribution, option, format!("{option}"));
+                        }
+                    });
+            });
+
+            if let Some(running) = &self.running {
+                let done = running.rows.len();
+                ui.add(ProgressBar::new(done as f32 / running.total as f32).text(format!("Running {done} of {}", running.total)));
+            }
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+            let Some(results) = &self.results else {
+                ui.label("Runs every algorithm on every input at lengths 16 to 1024");
+                return;
+            };
+            let methods = BenchConfig::default().algorithms;
+            // Log-log axes, so the slope of each line is the exponent of its growth
+            Plot::new("bench_plot")
+                .legend(Legend::default())
+                .height(300.0)
+                .x_axis_label("log2 n")
+                .y_axis_label(format!("log2 {}", self.metric).to_lowercase())
+                .show(ui, |plot| {
+                    for &method in &methods {
+                        let points: PlotPoints = results
+                            .series(method, self.distribution, self.metric)
+                            .into_iter()
+                            .filter(|&(_, y)| y > 0)
+                            .map(|(n, y)| [(n as f64).log2(), (y as f64).log2()])
+                            .collect();
+                        plot.line(Line::new(points).name(method.name()));
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Copy as CSV").clicked() {
+                    ui.output_mut(|o| o.copied_text = results.to_csv());
+                }
+                if ui.button("Copy as markdown").clicked() {
+                    ui.output_mut(|o| o.copied_text = results.to_markdown());
+                }
+            });
+            egui::Grid::new("bench_fits").striped(true).show(ui, |ui| {
+                for &method in &methods {
+                    ui.label(method.name());
+                    match Fit::best(&results.series(method, self.distribution, self.metric)) {
+                        Some(fit) => ui.label(format!("{}", fit)),
+                        None => ui.label("-"),
+                    };
+                    ui.end_row();
+                }
+            });
+        });
+        self.open = open;
+    }
+}
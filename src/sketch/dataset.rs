@@ -0,0 +1,67 @@
+use crate::sketch::methods::SortMethod;
+use crate::sketch::player::{is_permutation_of, starting};
+use crate::sketch::trace::TraceStream;
+use crate::sketch::List;
+use nannou::rand::{thread_rng, Rng};
+use std::fmt::Formatter;
+use strum_macros::{EnumIter, IntoStaticStr};
+
+/// Kinds of element the app can sort. Anything other than integers is drawn by rank
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Dataset {
+    /// Every integer in `0..length` once
+    Permutation,
+    /// Integers with only eight distinct values, so stability is visible
+    FewUnique,
+    /// Uniformly random floats
+    Floats,
+    /// Random three letter words, compared alphabetically
+    Words,
+    /// (key, payload) records compared by key only, with eight distinct keys
+    Records,
+}
+
+impl Dataset {
+    /// Starts generating the trace of `method` sorting `length` elements, returning the levels the
+    /// trace starts from. Integer datasets reuse `previous` if it holds the same values
+    pub(crate) fn start(&self, method: SortMethod, length: usize, shuffle: bool, previous: Vec<usize>) -> (Vec<usize>, TraceStream) {
+        let mut rng = thread_rng();
+        match self {
+            Dataset::Permutation | Dataset::FewUnique => {
+                let fresh = starting(length, *self == Dataset::FewUnique);
+                let reusable = previous.len() == length && is_permutation_of(&previous, &fresh);
+                let input = if reusable { previous } else { fresh };
+                start(List::new(input, length), method, shuffle || !reusable)
+            }
+            Dataset::Floats => {
+                let floats = (0..length).map(|_| rng.r#gen::<f64>()).collect();
+                start(List::with_order(floats, f64::total_cmp), method, false)
+            }
+            Dataset::Words => {
+                let words = (0..length)
+                    .map(|_| (0..3).map(|_| rng.gen_range(b'a'..=b'z') as char).collect())
+                    .collect();
+                start(List::with_order(words, String::cmp), method, false)
+            }
+            Dataset::Records => {
+                let records = (0..length).map(|_| (rng.gen_range(0..8_u8), rng.r#gen::<u32>())).collect();
+                start(List::with_order(records, |a: &(u8, u32), b| a.0.cmp(&b.0)), method, false)
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl std::fmt::Display for Dataset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+fn start<T: Clone + Send + Sync + 'static>(list: List<T>, method: SortMethod, shuffle: bool) -> (Vec<usize>, TraceStream) {
+    (list.levels().to_vec(), TraceStream::new(list, method, shuffle))
+}
@@ -0,0 +1,58 @@
+use nannou::rand::seq::SliceRandom;
+use nannou::rand::Rng;
+use std::fmt::Formatter;
+use strum_macros::{EnumIter, EnumString, IntoStaticStr};
+
+/// Shapes of input the algorithms can be run on, values are always in `0..length`
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum InputDistribution {
+    Shuffled,
+    Sorted,
+    Reversed,
+    /// Sorted, then roughly one element in twenty swapped with a close neighbour
+    NearlySorted,
+    /// Only eight distinct values, each repeated
+    FewUnique,
+}
+
+impl InputDistribution {
+    pub fn generate(&self, length: usize, rng: &mut impl Rng) -> Vec<usize> {
+        let mut v: Vec<usize> = (0..length).collect();
+        match self {
+            InputDistribution::Shuffled => v.shuffle(rng),
+            InputDistribution::Sorted => {}
+            InputDistribution::Reversed => v.reverse(),
+            InputDistribution::NearlySorted => {
+                if length > 1 {
+                    for _ in 0..(length / 20).max(1) {
+                        let i = rng.gen_range(0..length);
+                        let j = (i + rng.gen_range(1..=4)).min(length - 1);
+                        v.swap(i, j);
+                    }
+                }
+            }
+            InputDistribution::FewUnique => {
+                v = few_unique(length);
+                v.shuffle(rng);
+            }
+        }
+        v
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// `0..length` rounded down into eight equal buckets, in order
+pub fn few_unique(length: usize) -> Vec<usize> {
+    let step = length.div_ceil(8).max(1);
+    (0..length).map(|x| x / step * step).collect()
+}
+
+impl std::fmt::Display for InputDistribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::sketch::player::SortPlayer;
+use crate::sketch::trace_io::{self, Trace, TraceFormat};
+#[cfg(not(target_family = "wasm"))]
+use crate::sketch::{audio::AudioModel, offline};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+#[cfg(target_family = "wasm")]
+use crate::sketch::op_log::OpLog;
+#[cfg(target_family = "wasm")]
+use crate::sketch::trace::{TraceEvent, TraceStream};
+
+/// Events of the trace read each frame while exporting on the web
+#[cfg(target_family = "wasm")]
+const EVENTS_PER_FRAME: usize = 1 << 16;
+
+/// An export that finishes over several frames, so a long trace doesn't stall playback while it is
+/// generated. Natively the trace is generated and saved on a worker thread, on the web (which has
+/// no threads) a slice of it is generated each frame and it is downloaded once complete
+pub(crate) struct Export {
+    /// Name of the file being exported, without its extension for a trace
+    name: String,
+    #[cfg(not(target_family = "wasm"))]
+    receiver: Receiver<String>,
+    #[cfg(target_family = "wasm")]
+    stream: TraceStream,
+    /// The trace so far
+    #[cfg(target_family = "wasm")]
+    trace: Trace,
+    #[cfg(target_family = "wasm")]
+    format: TraceFormat,
+}
+
+impl Export {
+    /// Starts exporting the trace of `player`, generated again from the start
+    pub(crate) fn trace(player: &SortPlayer, format: TraceFormat) -> Self {
+        let name = player.export_name();
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let record = player.recorder();
+            let (sender, receiver) = mpsc::channel();
+            let file = name.clone();
+            std::thread::spawn(move || sender.send(saved(&record(), format, &file)));
+            Self { name, receiver }
+        }
+        #[cfg(target_family = "wasm")]
+        {
+            let (length, starting_vec) = (player.length, player.starting_vec.clone());
+            let trace = Trace { length, starting_vec, operations: OpLog::new(), phase_boundaries: vec![] };
+            Self { name, stream: player.fresh_trace(), trace, format }
+        }
+    }
+
+    /// Starts rendering the trace of `player`, generated again from the start, to a WAV file
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn audio(player: &SortPlayer, ops_per_second: f64, settings: AudioModel) -> Self {
+        let name = format!("{}.wav", player.export_name());
+        let record = player.recorder();
+        let (sender, receiver) = mpsc::channel();
+        let path = name.clone();
+        std::thread::spawn(move || {
+            sender.send(match offline::export_wav(&record(), path.as_ref(), ops_per_second, settings) {
+                Ok(()) => format!("Exported {path}"),
+                Err(e) => format!("Failed to export audio to {path}: {e:#}"),
+            })
+        });
+        Self { name, receiver }
+    }
+
+    /// Shown while the export runs
+    pub(crate) fn progress(&self) -> String {
+        #[cfg(not(target_family = "wasm"))]
+        return format!("Exporting {}...", self.name);
+        #[cfg(target_family = "wasm")]
+        format!("Exporting {} ({} ops so far)...", self.name, self.trace.operations.len())
+    }
+
+    /// How the export went, once it has finished
+    pub(crate) fn poll(&mut self) -> Option<String> {
+        #[cfg(not(target_family = "wasm"))]
+        return match self.receiver.try_recv() {
+            Ok(status) => Some(status),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(format!("Failed to export {}: the export stopped", self.name)),
+        };
+        #[cfg(target_family = "wasm")]
+        {
+            for _ in 0..EVENTS_PER_FRAME {
+                match self.stream.next() {
+                    Some(TraceEvent::Op(op)) => self.trace.operations.push(op),
+                    Some(TraceEvent::Phase) => {
+                        let point = self.trace.operations.len();
+                        if self.trace.phase_boundaries.last() != Some(&point) {
+                            self.trace.phase_boundaries.push(point);
+                        }
+                    }
+                    Some(_) => {}
+                    None => return Some(saved(&self.trace, self.format, &self.name)),
+                }
+            }
+            None
+        }
+    }
+}
+
+/// Saves `trace`, describing how it went
+fn saved(trace: &Trace, format: TraceFormat, name: &str) -> String {
+    match trace_io::save(trace, format, name) {
+        Ok(file) => format!("Exported {} ({} ops)", file, trace.operations.len()),
+        Err(e) => format!("Failed to export {}: {:#}", name, e),
+    }
+}
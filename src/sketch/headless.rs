@@ -0,0 +1,72 @@
+use crate::sketch::distribution::InputDistribution;
+use crate::sketch::list::Operation;
+use crate::sketch::methods::SortMethod;
+use crate::sketch::op_log::OpLog;
+use crate::sketch::trace::block_on;
+use crate::sketch::trace_io::Trace;
+use crate::sketch::List;
+use nannou::rand::rngs::SmallRng;
+use nannou::rand::SeedableRng;
+
+/// How many of each kind of operation a trace contains
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct OpCounts {
+    pub comparisons: usize,
+    pub reads: usize,
+    pub writes: usize,
+    pub swaps: usize,
+}
+
+impl OpCounts {
+    /// Counts the ops in `log`, which doesn't record comparisons
+    pub fn of(log: &OpLog) -> Self {
+        let mut counts = Self::default();
+        for op in log.iter() {
+            match op {
+                Operation::Get(_) => counts.reads += 1,
+                Operation::Set(..) => counts.writes += 1,
+                Operation::Swap(..) => counts.swaps += 1,
+            }
+        }
+        counts
+    }
+    pub fn total(&self) -> usize {
+        self.reads + self.writes + self.swaps
+    }
+}
+
+/// One algorithm run on one input, without a window or audio
+pub struct SortRun {
+    pub input: Vec<usize>,
+    pub list: List,
+}
+
+impl SortRun {
+    /// Sorts an input of `length` drawn from `distribution`. The same seed gives the same input
+    pub fn new(method: SortMethod, distribution: InputDistribution, length: usize, seed: u64) -> anyhow::Result<Self> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let input = distribution.generate(length, &mut rng);
+        let mut list = List::new(input.clone(), length);
+        // Always run the sort, even on sorted input, so its cost is counted
+        block_on(method.run(&mut list))?;
+        Ok(Self { input, list })
+    }
+
+    pub fn counts(&self) -> OpCounts {
+        OpCounts {
+            comparisons: self.list.comparisons,
+            ..OpCounts::of(&self.list.record_of_operations)
+        }
+    }
+
+    // Only the sort_bin command line exports headless runs, not the wasm library
+    #[allow(dead_code)]
+    pub fn trace(self) -> Trace {
+        Trace {
+            length: self.list.length,
+            starting_vec: self.input,
+            operations: self.list.record_of_operations,
+            phase_boundaries: self.list.phase_boundaries,
+        }
+    }
+}
@@ -1,14 +1,52 @@
+use std::cmp::Ordering;
+use std::fmt::Formatter;
 use std::ops::Range;
 use std::slice::Iter;
-use nannou::rand::Rng;
+use nannou::rand::rngs::SmallRng;
+use nannou::rand::{Rng, SeedableRng};
+use crate::sketch::op_log::OpLog;
+use crate::sketch::trace::{send, TraceEvent, TraceSink};
 
-#[derive(Debug, Clone)]
-pub struct List {
-    internal_vec: Vec<usize>,
-    pub(crate) record_of_operations: Vec<Operation>,
+/// Elements being sorted, with every access recorded as an `Operation`. Elements can be any type
+/// with an ordering; the trace records each one as its level, see `Tagged`
+#[derive(Clone)]
+pub struct List<T = usize> {
+    internal_vec: Vec<T>,
+    /// How elements are ordered, counted by `ListPart::compare`
+    order: fn(&T, &T) -> Ordering,
+    /// The level of each element, moved along with it
+    levels: Vec<usize>,
+    /// Where each element was when sorting started, see `Tagged`
+    origins: Vec<usize>,
+    /// `first_positions` of the levels when origins were last taken, from which playback predicts
+    /// the origins of sets that don't send one
+    predicted_origins: Option<Vec<usize>>,
+    pub(crate) record_of_operations: OpLog,
+    /// Ops recorded so far, whether they were kept in `record_of_operations` or streamed
+    ops_recorded: usize,
+    /// Op indices at which the algorithm started a new phase (a pass, a merge, a partition...)
+    pub(crate) phase_boundaries: Vec<usize>,
     pub(crate) length: usize,
+    /// Comparisons made through `ListPart::compare`, these aren't part of the trace
+    pub(crate) comparisons: usize,
+    /// When set, operations are streamed to playback instead of being recorded
+    pub(crate) sink: Option<TraceSink>,
+    /// Source of the shuffles, cloned along with the list so a copy makes the same ones
+    rng: SmallRng,
 }
 
+/// An element along with where it was when sorting started, which tells equal keys apart, and its
+/// level: the value in `0..length` recorded for it in the trace, which renderers draw as its height
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Tagged<T = usize> {
+    pub value: T,
+    pub origin: usize,
+    pub level: usize,
+}
+
+/// Origin of an element written by a trace that didn't record origins, e.g. an imported one
+pub(crate) const UNKNOWN_ORIGIN: usize = usize::MAX;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub(crate) enum Operation {
     Get(usize),
@@ -17,133 +55,334 @@ pub(crate) enum Operation {
 }
 
 impl List {
+    /// A list of integers, each of which is its own level
     pub fn new(vec: Vec<usize>, length: usize) -> Self {
+        let levels = vec.clone();
+        Self::with_levels(vec, length, usize::cmp, levels)
+    }
+}
+
+impl<T: Clone> List<T> {
+    /// A list ordered by `order`. Elements are normalised to their rank, the number of elements
+    /// ordered before them, so equal keys share a level and levels are in `0..length`
+    pub fn with_order(vec: Vec<T>, order: fn(&T, &T) -> Ordering) -> Self {
+        let levels = ranks(&vec, order);
+        Self::with_levels(vec.clone(), vec.len(), order, levels)
+    }
+    fn with_levels(vec: Vec<T>, length: usize, order: fn(&T, &T) -> Ordering, levels: Vec<usize>) -> Self {
         Self {
-            internal_vec: vec.clone(),
-            record_of_operations: vec![],
+            origins: (0..vec.len()).collect(),
+            predicted_origins: None,
+            internal_vec: vec,
+            order,
+            levels,
+            record_of_operations: OpLog::new(),
+            ops_recorded: 0,
+            phase_boundaries: vec![],
             length,
+            comparisons: 0,
+            sink: None,
+            rng: SmallRng::from_entropy(),
         }
     }
-    fn record(&mut self, operation: Operation) {
-        self.record_of_operations.push(operation);
+    async fn record(&mut self, operation: Operation) -> Result<(), Stopped> {
+        self.ops_recorded += 1;
+        match &self.sink {
+            Some(sink) => send(sink, TraceEvent::Op(operation)).await?,
+            None => self.record_of_operations.push(operation),
+        }
+        Ok(())
+    }
+    /// Writes `x` to `i`, sending where it came from before the set itself unless playback can
+    /// predict it
+    async fn write(&mut self, i: usize, x: Tagged<T>) -> Result<(), Stopped> {
+        self.check(i, || format!("set({i})"))?;
+        let predicted = self.predicted_origins.as_ref().map(|p| p[x.level]);
+        if let Some(sink) = &self.sink
+            && predicted != Some(x.origin)
+        {
+            send(sink, TraceEvent::Origin(x.origin)).await?;
+        }
+        self.record(Operation::Set(i, x.level)).await?;
+        self.internal_vec[i] = x.value;
+        self.origins[i] = x.origin;
+        self.levels[i] = x.level;
+        Ok(())
+    }
+    /// A `BoundsError` unless `i` is within the list
+    fn check(&self, i: usize, access: impl FnOnce() -> String) -> Result<(), Stopped> {
+        if i >= self.length {
+            return Err(self.out_of_bounds(access(), 0..self.length, None));
+        }
+        Ok(())
+    }
+    /// The error that stops the algorithm, for `SortMethod::run` to report
+    fn out_of_bounds(&self, access: String, valid: Range<usize>, slice: Option<Range<usize>>) -> Stopped {
+        Stopped::OutOfBounds(BoundsError { op_index: self.ops_recorded, access, valid, slice })
+    }
+    /// Makes each element's current position its origin, e.g. once the input has been shuffled
+    pub(crate) async fn retag(&mut self) -> Result<(), Stopped> {
+        self.origins = (0..self.internal_vec.len()).collect();
+        self.predicted_origins = Some(first_positions(&self.levels));
+        if let Some(sink) = &self.sink {
+            send(sink, TraceEvent::Retag).await?;
+        }
+        Ok(())
     }
     #[allow(dead_code)]
-    pub(crate) fn iter(&self) -> Iter<'_, usize> {
+    pub(crate) fn iter(&self) -> Iter<'_, T> {
         self.internal_vec.iter()
     }
 
+    /// Bytes of heap held by the list, not counting any the elements themselves point to
+    pub(crate) fn memory_bytes(&self) -> usize {
+        let indices = self.levels.capacity() + self.origins.capacity() + self.phase_boundaries.capacity()
+            + self.predicted_origins.as_ref().map_or(0, Vec::capacity);
+        self.internal_vec.capacity() * size_of::<T>() + indices * size_of::<usize>() + self.record_of_operations.memory_bytes()
+    }
+
+    /// The level of each element, which is what the trace records
+    pub(crate) fn levels(&self) -> &[usize] {
+        &self.levels
+    }
+
     pub fn is_sorted(&self) -> bool {
-        self.internal_vec.is_sorted()
+        self.internal_vec.is_sorted_by(|a, b| (self.order)(a, b).is_le())
+    }
+
+    /// Whether equal values are still in the order they started in
+    pub fn is_stable(&self) -> bool {
+        is_stable(&self.levels, &self.origins)
     }
 
-    pub fn is_sorted_visible(&mut self) -> bool {
-        for i in 0..(self.length-1) {
-            if self.get(i) > self.get(i+1) {
-                return false
+    pub async fn is_sorted_visible(&mut self) -> Result<bool, Stopped> {
+        for i in 1..self.length {
+            let (a, b) = (self.get(i-1).await?, self.get(i).await?);
+            if self.compare((i - 1, &a), (i, &b)).await?.is_gt() {
+                return Ok(false)
             }
         }
-        true
+        Ok(true)
     }
 }
 
-impl ListPart for List {
-    fn get(&mut self, i: usize) -> usize {
-        self.record(Operation::Get(i));
-        self.internal_vec[i]
+impl<T: Clone> ListPart<T> for List<T> {
+    async fn get(&mut self, i: usize) -> Result<T, Stopped> {
+        self.check(i, || format!("get({i})"))?;
+        self.record(Operation::Get(i)).await?;
+        Ok(self.internal_vec[i].clone())
     }
-    fn set(&mut self, i: usize, x: usize) {
-        self.record(Operation::Set(i, x));
-        self.internal_vec[i] = x;
-    }
-    fn swap(&mut self, i: usize, j: usize) {
-        self.record(Operation::Swap(i, j));
+    async fn swap(&mut self, i: usize, j: usize) -> Result<(), Stopped> {
+        self.check(i.max(j), || format!("swap({i}, {j})"))?;
+        self.record(Operation::Swap(i, j)).await?;
         self.internal_vec.swap(i, j);
+        self.origins.swap(i, j);
+        self.levels.swap(i, j);
+        Ok(())
+    }
+    async fn get_tagged(&mut self, i: usize) -> Result<Tagged<T>, Stopped> {
+        Ok(Tagged { value: self.get(i).await?, origin: self.origins[i], level: self.levels[i] })
     }
-    fn slice(&mut self, range: Range<usize>) -> SliceOfList {
-        SliceOfList { range, list: self }
+    async fn set_tagged(&mut self, i: usize, x: Tagged<T>) -> Result<(), Stopped> {
+        self.write(i, x).await
+    }
+    fn slice(&mut self, range: Range<usize>) -> Result<SliceOfList<'_, T>, Stopped> {
+        if range.start > range.end || range.end > self.length {
+            return Err(self.out_of_bounds(format!("slice({range:?})"), 0..self.length, None));
+        }
+        Ok(SliceOfList { range, list: self })
     }
     fn len(&self) -> usize {
         self.length
     }
+    async fn compare(&mut self, (i, a): (usize, &T), (j, b): (usize, &T)) -> Result<Ordering, Stopped> {
+        self.check(i.max(j), || format!("compare({i}, {j})"))?;
+        self.comparisons += 1;
+        if let Some(sink) = &self.sink {
+            send(sink, TraceEvent::Compare(i)).await?;
+            send(sink, TraceEvent::Compare(j)).await?;
+        }
+        Ok((self.order)(a, b))
+    }
+    async fn mark_phase(&mut self) -> Result<(), Stopped> {
+        if let Some(sink) = &self.sink {
+            return send(sink, TraceEvent::Phase).await;
+        }
+        let point = self.record_of_operations.len();
+        if self.phase_boundaries.last() != Some(&point) {
+            self.phase_boundaries.push(point);
+        }
+        Ok(())
+    }
 }
 
-pub struct SliceOfList<'a> {
+pub struct SliceOfList<'a, T = usize> {
     range: Range<usize>,
-    list: &'a mut List,
+    list: &'a mut List<T>,
+}
+
+impl<T: Clone> SliceOfList<'_, T> {
+    /// The list index of slice index `i`, or a `BoundsError` if it's outside the slice
+    fn index(&self, i: usize, access: impl FnOnce() -> String) -> Result<usize, Stopped> {
+        if i >= self.range.len() {
+            return Err(self.list.out_of_bounds(access(), 0..self.range.len(), Some(self.range.clone())));
+        }
+        Ok(self.range.start + i)
+    }
+}
+
+/// An access outside the list or slice an algorithm was working on
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundsError {
+    /// Index the offending op would have had in the trace
+    pub op_index: usize,
+    /// The offending call, with the indices it was given, e.g. `get(7)`
+    pub access: String,
+    /// Indices the call could have used
+    pub valid: Range<usize>,
+    /// Where the slice the call was made on sits in the list, if it was made on a slice
+    pub slice: Option<Range<usize>>,
 }
 
-impl SliceOfList<'_> {
-    fn sliced(&mut self) -> &mut [usize] {
-        &mut self.list.internal_vec[self.range.clone()]
+impl std::fmt::Display for BoundsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.slice {
+            Some(slice) => write!(f, "op {}: {} is outside slice {:?} (valid indices {:?})", self.op_index, self.access, slice, self.valid),
+            None => write!(f, "op {}: {} is outside the list (valid indices {:?})", self.op_index, self.access, self.valid),
+        }
     }
-    fn record(&mut self, operation: Operation) {
-        let slice_start = self.range.start;
-        let operation = match operation {
-            Operation::Get(i) => Operation::Get(i + slice_start),
-            Operation::Set(i, x) => Operation::Set(i + slice_start, x),
-            Operation::Swap(i, j) => Operation::Swap(i + slice_start, j + slice_start),
-        };
-        self.list.record(operation);
+}
+
+impl std::error::Error for BoundsError {}
+
+/// Why an algorithm stopped before finishing. Every list access can fail, and algorithms pass the
+/// error straight back with `?`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stopped {
+    OutOfBounds(BoundsError),
+    /// Playback no longer wants the ops, e.g. because the player was dropped
+    Cancelled,
+}
+
+impl std::fmt::Display for Stopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stopped::OutOfBounds(e) => write!(f, "{e}"),
+            Stopped::Cancelled => write!(f, "cancelled"),
+        }
     }
 }
 
-pub trait ListPart {
-    fn get(&mut self, i: usize) -> usize;
-    fn set(&mut self, i: usize, x: usize);
-    fn swap(&mut self, i: usize, j: usize);
-    fn slice(&mut self, range: Range<usize>) -> SliceOfList;
+impl std::error::Error for Stopped {}
+
+/// What an algorithm sorts, a whole `List` or a slice of one. Accesses are async so a streaming sort
+/// can pause between any two of them until playback catches up
+pub trait ListPart<T = usize> {
+    async fn get(&mut self, i: usize) -> Result<T, Stopped>;
+    async fn swap(&mut self, i: usize, j: usize) -> Result<(), Stopped>;
+    /// Reads an element along with its origin, for algorithms that move elements with `set_tagged`
+    async fn get_tagged(&mut self, i: usize) -> Result<Tagged<T>, Stopped>;
+    /// Writes an element, keeping its origin
+    async fn set_tagged(&mut self, i: usize, x: Tagged<T>) -> Result<(), Stopped>;
+    fn slice(&mut self, range: Range<usize>) -> Result<SliceOfList<'_, T>, Stopped>;
     fn len(&self) -> usize;
+    /// Compares two values read from the list, each given with the index it was read from so
+    /// playback can show which elements were compared. Counts the comparison
+    async fn compare(&mut self, a: (usize, &T), b: (usize, &T)) -> Result<Ordering, Stopped>;
+    /// Marks the current point in the trace as the start of a new phase of the algorithm
+    async fn mark_phase(&mut self) -> Result<(), Stopped>;
 }
 
-impl ListPart for SliceOfList<'_> {
-    fn get(&mut self, i: usize) -> usize {
-        self.record(Operation::Get(i));
-        self.sliced()[i]
+impl<T: Clone> ListPart<T> for SliceOfList<'_, T> {
+    async fn get(&mut self, i: usize) -> Result<T, Stopped> {
+        let i = self.index(i, || format!("get({i})"))?;
+        self.list.get(i).await
     }
-    fn set(&mut self, i: usize, x: usize) {
-        self.record(Operation::Set(i, x));
-        self.sliced()[i] = x;
+    async fn swap(&mut self, i: usize, j: usize) -> Result<(), Stopped> {
+        let access = || format!("swap({i}, {j})");
+        let (i, j) = (self.index(i, access)?, self.index(j, access)?);
+        self.list.swap(i, j).await
     }
-    fn swap(&mut self, i: usize, j: usize) {
-        self.record(Operation::Swap(i, j));
-        self.sliced().swap(i, j);
+    async fn get_tagged(&mut self, i: usize) -> Result<Tagged<T>, Stopped> {
+        let i = self.index(i, || format!("get({i})"))?;
+        self.list.get_tagged(i).await
     }
-    fn slice(&mut self, new: Range<usize>) -> SliceOfList {
+    async fn set_tagged(&mut self, i: usize, x: Tagged<T>) -> Result<(), Stopped> {
+        let i = self.index(i, || format!("set({i})"))?;
+        self.list.write(i, x).await
+    }
+    fn slice(&mut self, new: Range<usize>) -> Result<SliceOfList<'_, T>, Stopped> {
+        if new.start > new.end || new.end > self.range.len() {
+            return Err(self.list.out_of_bounds(format!("slice({new:?})"), 0..self.range.len(), Some(self.range.clone())));
+        }
         let new_start = new.start + self.range.start;
         let new_end = new.end + self.range.start;
-        assert!(new_end <= self.range.end);
-        SliceOfList {
+        Ok(SliceOfList {
             range: new_start..new_end,
-            list: &mut self.list,
-        }
+            list: self.list,
+        })
     }
     fn len(&self) -> usize {
         self.range.len()
     }
+    async fn compare(&mut self, (i, a): (usize, &T), (j, b): (usize, &T)) -> Result<Ordering, Stopped> {
+        let access = || format!("compare({i}, {j})");
+        let (i, j) = (self.index(i, access)?, self.index(j, access)?);
+        self.list.compare((i, a), (j, b)).await
+    }
+    async fn mark_phase(&mut self) -> Result<(), Stopped> {
+        self.list.mark_phase().await
+    }
 }
 
-pub fn shuffle_step_by_step(list: &mut List) {
-    let mut rng = nannou::rand::thread_rng();
+pub async fn shuffle_step_by_step<T: Clone>(list: &mut List<T>) -> Result<(), Stopped> {
     if list.len() <= 1 {
-        return;
+        return Ok(());
     }
     let mut i = list.len() - 1;
     loop {
-        let j = rng.gen_range(0..=i);
+        let j = list.rng.gen_range(0..=i);
         if i != j {
-            list.swap(i, j);
+            list.swap(i, j).await?;
         }
         if i == 0 {
             break;
         }
         i -= 1;
     }
+    Ok(())
+}
+
+/// Whether every run of equal `values` has ascending, known `origins`
+pub(crate) fn is_stable(values: &[usize], origins: &[usize]) -> bool {
+    let tagged = values.iter().zip(origins);
+    tagged.clone().zip(tagged.skip(1)).all(|((a, i), (b, j))| {
+        a != b || (i < j && *j != UNKNOWN_ORIGIN)
+    })
+}
+
+/// Where each level first appears in `levels`, which is the origin of every element of a level
+/// with no equal elements
+pub(crate) fn first_positions(levels: &[usize]) -> Vec<usize> {
+    let mut first = vec![UNKNOWN_ORIGIN; levels.iter().max().map_or(0, |&l| l + 1)];
+    for (i, &level) in levels.iter().enumerate().rev() {
+        first[level] = i;
+    }
+    first
+}
+
+/// The number of elements of `vec` ordered before each element
+fn ranks<T>(vec: &[T], order: fn(&T, &T) -> Ordering) -> Vec<usize> {
+    let mut sorted: Vec<&T> = vec.iter().collect();
+    sorted.sort_by(|a, b| order(a, b));
+    vec.iter().map(|x| sorted.partition_point(|y| order(y, x).is_lt())).collect()
 }
 
-pub fn zing(list: &mut List) {
+pub async fn zing<T: Clone>(list: &mut List<T>) -> Result<(), Stopped> {
     for i in 0..list.len() {
-        let _ = list.get(i);
+        list.get(i).await?;
     }
+    Ok(())
 }
 
 // pub fn insert_pause(list: &mut List, ticks: usize) {
@@ -1,12 +1,12 @@
 use crate::sketch::algorithms::{bogo, bubble, bucket, mergesort, quicksort, radix, selection};
-use crate::sketch::player::SortPlayer;
-use crate::sketch::{renderers, List};
-use nannou::Draw;
+use crate::sketch::{List, Stopped};
+use anyhow::anyhow;
 use std::fmt::Formatter;
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
+use strum_macros::{EnumIter, EnumString, IntoStaticStr};
 
-#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
 pub enum SortMethod {
     Quick,
     Merge,
@@ -20,72 +20,63 @@ pub enum SortMethod {
 }
 
 impl SortMethod {
-    pub fn func(&self) -> fn(&mut List) {
+    pub async fn sort<T: Clone>(&self, list: &mut List<T>) -> Result<(), Stopped> {
         match self {
-            SortMethod::Quick => quicksort::sort,
-            SortMethod::Merge => mergesort::sort,
-            SortMethod::Bubble => bubble::sort,
-            SortMethod::Selection => selection::sort,
-            SortMethod::RadixBase2 => |x| radix::sort(x, 2),
-            SortMethod::RadixBase16 => |x| radix::sort(x, 16),
-            SortMethod::RadixBase10 => |x| radix::sort(x, 10),
-            SortMethod::Bucket => bucket::sort,
-            &SortMethod::Bogo => bogo::sort,
+            SortMethod::Quick => quicksort::sort(list).await,
+            SortMethod::Merge => mergesort::sort(list).await,
+            SortMethod::Bubble => bubble::sort(list).await,
+            SortMethod::Selection => selection::sort(list).await,
+            SortMethod::RadixBase2 => radix::sort(list, 2).await,
+            SortMethod::RadixBase16 => radix::sort(list, 16).await,
+            SortMethod::RadixBase10 => radix::sort(list, 10).await,
+            SortMethod::Bucket => bucket::sort(list).await,
+            &SortMethod::Bogo => bogo::sort(list).await,
         }
     }
+    /// Sorts `list`, reporting an access outside the list or a slice of it as an error
+    pub async fn run<T: Clone>(&self, list: &mut List<T>) -> anyhow::Result<()> {
+        self.sort(list).await.map_err(|e| anyhow!("{} sort: {}", self.name(), e))
+    }
     pub fn index(&self) -> usize {
         Self::iter().enumerate().find(|(_, x)| x == self).unwrap().0
     }
-}
-
-impl std::fmt::Display for SortMethod {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} (F{})", self, self.index() + 1)
-    }
-}
-
-#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
-pub enum RenderMethod {
-    Classic,
-    DisparityDots,
-    ColourCircle,
-    ColourTowers,
-}
-
-impl RenderMethod {
-    pub fn func(&self) -> fn(player: &SortPlayer, draw: &Draw, aspect: f32) {
+    /// Whether equal values keep their relative order
+    pub fn is_stable(&self) -> bool {
         match self {
-            RenderMethod::Classic => renderers::classic::draw_state,
-            RenderMethod::DisparityDots => renderers::disparity_dots::draw_state,
-            RenderMethod::ColourCircle => renderers::colour_circle::draw_state,
-            RenderMethod::ColourTowers => renderers::colour_towers::draw_state,
+            SortMethod::Merge | SortMethod::Bubble | SortMethod::Bucket => true,
+            SortMethod::RadixBase2 | SortMethod::RadixBase16 | SortMethod::RadixBase10 => true,
+            SortMethod::Quick | SortMethod::Selection | SortMethod::Bogo => false,
         }
     }
-    pub fn draw(&self, player: &SortPlayer, draw: &Draw, aspect: f32) {
-        self.func()(player, draw, aspect);
-    }
-    pub fn index(&self) -> usize {
-        Self::iter().enumerate().find(|(_, x)| x == self).unwrap().0
+    /// Name used on the command line
+    pub fn name(&self) -> &'static str {
+        self.into()
     }
 }
 
-impl std::fmt::Display for RenderMethod {
+impl std::fmt::Display for SortMethod {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} ({})", self, self.index() + 1)
+        write!(f, "{:?} (F{})", self, self.index() + 1)
     }
 }
 
 #[macro_export]
 macro_rules! restart {
-    ($x:expr) => {
+    ($x:expr) => {{
+        let breakpoints = $x.player.breakpoints.clone();
         $x.player = SortPlayer::new(
             2_usize.pow($x.length_log2 as u32),
-            $x.sorter.func(),
+            $x.sorter,
             $x.player.playback_rate,
             $x.reshuffle_on_change,
+            $x.dataset,
             $x.player.playback_vec.clone()
-        )
-    };
+        );
+        $x.player.breakpoints = breakpoints;
+        $x.scheduler.reset();
+        $x.renderers.on_restart(&$x.player);
+        $x.sortedness.clear();
+    }};
 }
 
 // pub fn restart(model: &mut Model) {
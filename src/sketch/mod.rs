@@ -1,6 +1,5 @@
-use crate::sketch::algorithms::*;
 use crate::sketch::player::SortPlayer;
-use crate::sketch::audio::AudioModel;
+use crate::sketch::audio::{AudioModel, PanMode};
 use egui::{ComboBox, Window};
 pub use list::*;
 use nannou::prelude::*;
@@ -8,7 +7,14 @@ use nannou::winit::event::VirtualKeyCode;
 use nannou_egui::egui::Slider;
 use nannou_egui::{self, egui, Egui};
 use strum::IntoEnumIterator;
-use crate::sketch::methods::{RenderMethod, SortMethod};
+use crate::sketch::methods::SortMethod;
+use crate::sketch::renderers::Renderers;
+use crate::sketch::bench_view::BenchView;
+use crate::sketch::sortedness_view::SortednessView;
+use crate::sketch::dataset::Dataset;
+use crate::sketch::scheduler::PlaybackScheduler;
+use crate::sketch::trace_io::{Trace, TraceFormat};
+use crate::sketch::export::Export;
 use nannou::wgpu::{Backends, DeviceDescriptor, Limits};
 use std::cell::RefCell;
 
@@ -18,8 +24,23 @@ mod algorithms;
 mod list;
 mod player;
 mod renderers;
-mod methods;
+pub(crate) mod methods;
+pub(crate) mod distribution;
+pub(crate) mod dataset;
+pub(crate) mod headless;
+pub(crate) mod bench;
+mod bench_view;
+mod sortedness;
+mod sortedness_view;
+mod scheduler;
+mod activity;
+mod trace;
+mod op_log;
+mod export;
+pub(crate) mod trace_io;
 pub mod audio;
+#[cfg(not(target_family = "wasm"))]
+mod offline;
 
 pub async fn run_app(width: u32, height: u32) {
     #[cfg(debug_assertions)]
@@ -68,40 +89,99 @@ async fn create_window(app: &App, width: u32, height: u32) {
         .unwrap();
 }
 
+const MIN_PLAYBACK_RATE: f64 = 0.1;
+const MAX_PLAYBACK_RATE: f64 = 10000.0;
+
 struct Model {
     player: SortPlayer,
     egui: Egui,
     sorter: SortMethod,
     length_log2: usize,
-    renderer: RenderMethod,
-    last_play: f32,
+    renderers: Renderers,
+    scheduler: PlaybackScheduler,
     audio: AudioModel,
     reshuffle_on_change: bool,
+    dataset: Dataset,
+    export_format: TraceFormat,
+    #[cfg(not(target_family = "wasm"))]
+    import_path: String,
+    /// Result of the last trace import, shown in the settings window
+    import_status: Option<String>,
+    /// Result of the last trace or audio export, shown in the settings window
+    export_status: Option<String>,
+    /// The export in progress, if there is one
+    export: Option<Export>,
+    bench: BenchView,
+    sortedness: SortednessView,
 }
 
 impl Model {
+    /// Replaces the player with a replay of the trace in `bytes`, reporting how it went
+    fn import_trace(&mut self, name: &str, bytes: anyhow::Result<Vec<u8>>) {
+        let trace = bytes.and_then(|bytes| Trace::decode(&bytes));
+        self.import_status = Some(match trace {
+            Ok(trace) => {
+                let breakpoints = self.player.breakpoints.clone();
+                let ops = trace.operations.len();
+                self.player = SortPlayer::from_trace(trace, self.player.playback_rate);
+                self.player.breakpoints = breakpoints;
+                self.scheduler.reset();
+                self.renderers.on_restart(&self.player);
+                self.sortedness.clear();
+                format!("Loaded {} ({} ops)", name, ops)
+            }
+            Err(e) => format!("Failed to load {}: {:#}", name, e),
+        });
+    }
+
     fn new(app: &App) -> Model {
         let egui = Egui::from_window(&app.main_window());
+        let mut renderers = Renderers::default();
+        let (width, height) = app.main_window().inner_size_points();
+        renderers.on_resize(width, height);
 
         Model {
-            player: SortPlayer::new(2_usize.pow(8), quicksort::sort, 50, true, vec![]),
+            player: SortPlayer::new(2_usize.pow(8), SortMethod::Quick, 50.0, true, Dataset::Permutation, vec![]),
             egui,
             sorter: SortMethod::Quick,
             length_log2: 8,
-            renderer: RenderMethod::Classic,
-            last_play: app.time,
+            renderers,
+            scheduler: PlaybackScheduler::default(),
             audio: AudioModel {
                 phase: 0.0,
                 hz: 440.0,
                 volume: 0.2,
+                ..Default::default()
             },
             reshuffle_on_change: true,
+            dataset: Dataset::Permutation,
+            export_format: TraceFormat::Json,
+            #[cfg(not(target_family = "wasm"))]
+            import_path: String::new(),
+            import_status: None,
+            export_status: None,
+            export: None,
+            bench: BenchView::default(),
+            sortedness: SortednessView::default(),
         }
     }
 }
 
 fn event(_app: &App, model: &mut Model, event: WindowEvent) {
+    #[cfg(not(target_family = "wasm"))]
+    if let DroppedFile(path) = &event
+        && !model.renderers.on_dropped_file(path)
+    {
+        model.import_trace(&path.display().to_string(), std::fs::read(path).map_err(Into::into));
+    }
+    if let Resized(size) = event {
+        model.renderers.on_resize(size.x, size.y);
+    }
     if let KeyPressed(key) = event {
+        // Don't treat typing into a breakpoint field as shortcuts
+        if model.egui.ctx().wants_keyboard_input() {
+            return;
+        }
         match key {
             VirtualKeyCode::Up => {
                 model.length_log2 += 1;
@@ -111,28 +191,32 @@ fn event(_app: &App, model: &mut Model, event: WindowEvent) {
                 model.length_log2 -= 1;
                 restart!(model);
             }
-            VirtualKeyCode::Right => {model.player.playback_rate *= 2}
-            VirtualKeyCode::Left => {model.player.playback_rate /= 2}
-            VirtualKeyCode::Space => {model.player.reset_play()}
+            VirtualKeyCode::Right => {model.player.playback_rate = (model.player.playback_rate * 2.0).min(MAX_PLAYBACK_RATE)}
+            VirtualKeyCode::Left => {model.player.playback_rate = (model.player.playback_rate / 2.0).max(MIN_PLAYBACK_RATE)}
+            VirtualKeyCode::Space => {
+                model.player.reset_play();
+                model.scheduler.reset();
+            }
+            VirtualKeyCode::P => model.player.toggle_pause(),
+            VirtualKeyCode::S => model.player.step(),
+            VirtualKeyCode::W => model.player.step_to_next_write(),
+            VirtualKeyCode::N => model.player.play_to_next_phase(),
             _ => {}
         }
 
+        // Key1 to Key9 come before Key0, so the keys pick renderers in the order they are printed
         let key = key as u32;
         if key < 10 {
-            RenderMethod::iter()
-                .nth(key as usize)
-                .map(|x| model.renderer = x);
+            model.renderers.select(key as usize);
         }
         else if key > 36 && key < 61 {
             let f_key = key - 37;
-            SortMethod::iter()
-                .nth(f_key as usize)
-                .map(|x| {
-                    if x != model.sorter {
-                        model.sorter = x;
-                        restart!(model);
-                    }
-                });
+            if let Some(x) = SortMethod::iter().nth(f_key as usize)
+                && x != model.sorter
+            {
+                model.sorter = x;
+                restart!(model);
+            }
         }
     }
 }
@@ -140,19 +224,20 @@ fn event(_app: &App, model: &mut Model, event: WindowEvent) {
 fn update(app: &App, model: &mut Model, update: Update) {
     gui(app, model, update);
 
-    let since_last = app.time - model.last_play;
-    if since_last < 1.0 / model.player.playback_rate as f32 {
-        // println!("Since last play = {:?}", since_last);
-        return
-    } else {
-        // println!("Played");
+    #[cfg(target_family = "wasm")]
+    if let Some((name, bytes)) = trace_io::take_picked() {
+        model.import_trace(&name, Ok(bytes));
+    }
+    if let Some(status) = model.export.as_mut().and_then(Export::poll) {
+        (model.export_status, model.export) = (Some(status), None);
     }
 
-    let raw_updates = model.player.playback_rate as f64 * update.since_last.as_secs_f64();
-    let moves = (raw_updates).ceil() as usize;
-
-    model.player.play(moves);
-    model.last_play = app.time;
+    let rate = model.player.playback_rate;
+    let moves = model.scheduler.due(update.since_last.as_secs_f64(), rate);
+    let played = model.player.play(moves);
+    model.scheduler.record(update.since_start.as_secs_f64(), played, rate);
+    let aspect = app.window_rect().x.len() / app.window_rect().y.len();
+    model.renderers.current_mut().update(&model.player, aspect);
 }
 
 fn gui(_app: &App, model: &mut Model, update: Update) {
@@ -160,14 +245,11 @@ fn gui(_app: &App, model: &mut Model, update: Update) {
     egui.set_elapsed_time(update.since_start);
     egui.begin_frame();
 
+    // Importing replaces the whole player, so it waits until the window has been laid out
+    #[cfg(not(target_family = "wasm"))]
+    let mut import = None;
     Window::new("Settings").show(egui.ctx(), |ui| {
-        ComboBox::from_label("Renderer")
-            .selected_text(format!("{}", model.renderer))
-            .show_ui(ui, |ui| {
-                for option in RenderMethod::iter() {
-                    ui.selectable_value(&mut model.renderer, option, format!("{option}"));
-                }
-            });
+        model.renderers.ui(ui);
         ComboBox::from_label("Algorithm")
             .selected_text(format!("{}", model.sorter))
             .show_ui(ui, |ui| {
@@ -179,8 +261,16 @@ fn gui(_app: &App, model: &mut Model, update: Update) {
                     }
                 }
             });
+        stability_badge(ui, &model.player);
 
-        ui.add(Slider::new(&mut model.player.playback_rate, 1..=10000).text("Playback rate (ops/secs) ⬅/➡"));
+        ui.add(
+            Slider::new(&mut model.player.playback_rate, MIN_PLAYBACK_RATE..=MAX_PLAYBACK_RATE)
+                .logarithmic(true)
+                .text("Playback rate (ops/secs) ⬅/➡"),
+        );
+        ui.label(format!("Measured rate: {:.1} ops/sec", model.scheduler.measured_rate()));
+        ui.label(format!("Frame rate: {:.0} fps", model.scheduler.frame_rate()));
+        playback_controls(ui, &mut model.player);
         let res = ui.add(Slider::new(&mut model.length_log2, 1..=16).text("Length (log2) ⬆/⬇"));
         if res.changed() {
             restart!(model);
@@ -189,10 +279,139 @@ fn gui(_app: &App, model: &mut Model, update: Update) {
             restart!(model);
         }
         ui.checkbox(&mut model.reshuffle_on_change, "Reshuffle array on algorithm change");
+        ComboBox::from_label("Data")
+            .selected_text(format!("{}", model.dataset))
+            .show_ui(ui, |ui| {
+                for option in Dataset::iter() {
+                    if ui.selectable_value(&mut model.dataset, option, format!("{option}")).changed() {
+                        restart!(model);
+                    }
+                }
+            });
         ui.add(Slider::new(&mut model.audio.volume, 0.0..=1.0).text("Volume"));
+        ComboBox::from_label("Stereo panning")
+            .selected_text(format!("{}", model.audio.pan_mode))
+            .show_ui(ui, |ui| {
+                for option in PanMode::iter() {
+                    ui.selectable_value(&mut model.audio.pan_mode, option, format!("{option}"));
+                }
+            });
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("export_format")
+                .selected_text(format!("{}", model.export_format))
+                .show_ui(ui, |ui| {
+                    for option in TraceFormat::iter() {
+                        ui.selectable_value(&mut model.export_format, option, format!("{option}"));
+                    }
+                });
+            if ui.add_enabled(model.export.is_none(), egui::Button::new("Export trace")).clicked() {
+                model.export = Some(Export::trace(&model.player, model.export_format));
+            }
+        });
+        if let Some(status) = model.export.as_ref().map(Export::progress).or_else(|| model.export_status.clone()) {
+            ui.label(status);
+        }
+        ui.horizontal(|ui| {
+            #[cfg(not(target_family = "wasm"))]
+            {
+                ui.text_edit_singleline(&mut model.import_path);
+                if ui.button("Import trace").clicked() {
+                    import = Some(model.import_path.clone());
+                }
+            }
+            #[cfg(target_family = "wasm")]
+            if ui.button("Import trace").clicked() {
+                trace_io::pick_file();
+            }
+        });
+        if let Some(status) = &model.import_status {
+            ui.label(status);
+        }
+        ui.toggle_value(&mut model.bench.open, "Benchmark");
+        ui.toggle_value(&mut model.sortedness.open, "Sortedness");
+        #[cfg(not(target_family = "wasm"))]
+        if ui.add_enabled(model.export.is_none(), egui::Button::new("Export audio (WAV)")).clicked() {
+            model.export = Some(Export::audio(&model.player, model.player.playback_rate, model.audio));
+        }
         let v = model.audio.volume;
-        model.player.stream.send(move |x| x.volume = v).unwrap();
+        let pan_mode = model.audio.pan_mode;
+        model.player.stream.send(move |x| {
+            x.volume = v;
+            x.pan_mode = pan_mode;
+        }).unwrap();
+    });
+    model.bench.show(egui.ctx());
+    model.sortedness.show(egui.ctx(), &model.player);
+
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(path) = import {
+        model.import_trace(&path, std::fs::read(&path).map_err(Into::into));
+    }
+}
+
+fn stability_badge(ui: &mut egui::Ui, player: &SortPlayer) {
+    ui.horizontal(|ui| {
+        // An imported trace may not come from the selected algorithm, so only the run is described
+        match player.method.map(|method| method.is_stable()) {
+            Some(true) => ui.colored_label(egui::Color32::LIGHT_GREEN, "stable"),
+            Some(false) => ui.colored_label(egui::Color32::LIGHT_RED, "unstable"),
+            None => ui.label("imported trace"),
+        };
+        // Only a finished sort can be checked, and only equal values can be out of order
+        if player.playback_complete() {
+            let order = if player.is_stable() { "kept" } else { "changed" };
+            ui.label(format!("order of equal values {order} in this run"));
+        }
+    });
+}
+
+fn playback_controls(ui: &mut egui::Ui, player: &mut SortPlayer) {
+    ui.separator();
+    let total = match player.total_ops() {
+        Some(total) => total.to_string(),
+        None => format!("{}+ (generating)", player.recent_ops.len()),
+    };
+    ui.label(format!("Op {} / {}", player.current_play_back_point, total));
+    if let Some(error) = player.trace_error() {
+        ui.colored_label(egui::Color32::LIGHT_RED, error);
+    }
+    let ops = player.recent_ops.len() - player.recent_ops.first();
+    ui.label(format!(
+        "Trace memory: {:.1} KiB, {:.1} KiB of it for the last {} ops ({:.1} KiB unpacked)",
+        player.trace_memory_bytes() as f64 / 1024.0,
+        player.recent_ops.memory_bytes() as f64 / 1024.0,
+        ops,
+        (ops * std::mem::size_of::<list::Operation>()) as f64 / 1024.0,
+    ));
+    ui.horizontal(|ui| {
+        let label = if player.paused { "Resume (P)" } else { "Pause (P)" };
+        if ui.button(label).clicked() {
+            player.toggle_pause();
+        }
+        if ui.button("Step (S)").clicked() {
+            player.step();
+        }
+        if ui.button("Next write (W)").clicked() {
+            player.step_to_next_write();
+        }
+        if ui.button("Next phase (N)").clicked() {
+            player.play_to_next_phase();
+        }
+    });
+
+    let max_index = player.length.saturating_sub(1);
+    let max_ops = player.total_ops().unwrap_or(usize::MAX);
+    let b = &mut player.breakpoints;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut b.on_write, "Break when index written");
+        ui.add(egui::DragValue::new(&mut b.write_index).clamp_range(0..=max_index));
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut b.on_op_count, "Break at op");
+        ui.add(egui::DragValue::new(&mut b.op_count).clamp_range(0..=max_ops));
     });
+    ui.checkbox(&mut b.on_phase, "Break at next phase boundary");
+    ui.separator();
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -203,9 +422,8 @@ fn view(app: &App, model: &Model, frame: Frame) {
         .scale_y(app.window_rect().y.len())
         .xy(Vec2::splat(-0.5));
 
-    (0.0, 1.0, 0.0, 1.0);
     draw.background().color(Srgb::new(20_u8, 20, 20));
-    model.renderer.draw(&model.player, &draw, aspect);
+    model.renderers.current().draw(&model.player, &draw, aspect);
     draw.to_frame(app, &frame).unwrap();
     model.egui.draw_to_frame(&frame).unwrap();
 }
@@ -0,0 +1,146 @@
+use crate::sketch::audio::AudioModel;
+use crate::sketch::player::{apply_op, note};
+use crate::sketch::trace_io::Trace;
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const CHANNELS: u16 = 2;
+
+/// Frames `render_trace` has produced by the end of the first `ops` ops
+fn total_frames(ops: usize, ops_per_second: f64, sample_rate: u32) -> u64 {
+    (ops as f64 * (sample_rate as f64 / ops_per_second)).round() as u64
+}
+
+/// Synthesises the whole of `trace`, passing interleaved samples to `emit` as they are
+/// produced. Every operation gets exactly `sample_rate / ops_per_second` frames (carrying the
+/// fractional part over to the next op)
+pub fn render_trace(
+    trace: &Trace,
+    ops_per_second: f64,
+    sample_rate: u32,
+    channels: u16,
+    settings: AudioModel,
+    mut emit: impl FnMut(f32) -> Result<()>,
+) -> Result<()> {
+    let channels = channels as usize;
+
+    let mut model = settings;
+    let mut vec = trace.starting_vec.clone();
+    let mut frames = 0;
+
+    for (n, op) in trace.operations.iter().enumerate() {
+        apply_op(&mut vec, op);
+        let (hz, index_prop) = note(op, &vec, trace.length);
+        model.hz = hz;
+        model.pan = model.pan_mode.pan(index_prop);
+
+        let end = total_frames(n + 1, ops_per_second, sample_rate);
+        while frames < end {
+            let s = model.next_sample(sample_rate as f64);
+            for c in 0..channels {
+                emit(model.channel_sample(s, c, channels))?;
+            }
+            frames += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the header of a 16-bit PCM WAV file holding `data_len` bytes of samples
+fn write_wav_header(w: &mut impl Write, data_len: u32, sample_rate: u32, channels: u16) -> Result<()> {
+    let block_align = channels * 2;
+
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVE")?;
+
+    w.write_all(b"fmt ")?;
+    w.write_all(&16_u32.to_le_bytes())?;
+    w.write_all(&1_u16.to_le_bytes())?; // PCM
+    w.write_all(&channels.to_le_bytes())?;
+    w.write_all(&sample_rate.to_le_bytes())?;
+    w.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&16_u16.to_le_bytes())?;
+
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())?;
+    Ok(())
+}
+
+/// Renders `trace` straight into a WAV file, without holding the samples in memory
+pub fn export_wav(trace: &Trace, path: &Path, ops_per_second: f64, settings: AudioModel) -> Result<()> {
+    let frames = total_frames(trace.operations.len(), ops_per_second, SAMPLE_RATE);
+    let data_len = frames * CHANNELS as u64 * 2;
+    // The RIFF size field counts the 36 header bytes after it as well as the samples
+    let Some(data_len) = u32::try_from(data_len).ok().filter(|&len| len <= u32::MAX - 36) else {
+        bail!("{data_len} bytes of audio is more than a WAV file can hold, try a faster playback rate");
+    };
+
+    let mut w = BufWriter::new(File::create(path)?);
+    write_wav_header(&mut w, data_len, SAMPLE_RATE, CHANNELS)?;
+    render_trace(trace, ops_per_second, SAMPLE_RATE, CHANNELS, settings, |s| {
+        let v = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        Ok(w.write_all(&v.to_le_bytes())?)
+    })?;
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sketch::list::Operation;
+
+    fn trace(ops: usize) -> Trace {
+        let operations = (0..ops).map(|k| if k % 2 == 0 { Operation::Get(k % 4) } else { Operation::Swap(k % 4, 0) }).collect();
+        Trace { length: 4, starting_vec: vec![3, 1, 2, 0], operations, phase_boundaries: vec![] }
+    }
+
+    fn frames_rendered(ops: usize, ops_per_second: f64) -> usize {
+        let mut samples = 0;
+        render_trace(&trace(ops), ops_per_second, SAMPLE_RATE, CHANNELS, AudioModel::default(), |_| {
+            samples += 1;
+            Ok(())
+        })
+        .unwrap();
+        samples / CHANNELS as usize
+    }
+
+    #[test]
+    fn every_op_gets_its_share_of_frames() {
+        assert_eq!(frames_rendered(7, 300.0), 7 * 147);
+        // 1.1025 frames per op, with the remainder carried over rather than dropped each op
+        assert_eq!(frames_rendered(7, 40_000.0), 8);
+        assert_eq!(frames_rendered(0, 300.0), 0);
+    }
+
+    #[test]
+    fn wav_header_describes_the_samples() {
+        let path = std::env::temp_dir().join(format!("offline_test_{}.wav", std::process::id()));
+        export_wav(&trace(10), &path, 100.0, AudioModel::default()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        // 441 frames per op, of two 16-bit samples
+        let data_len = 10 * 441 * 4;
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + data_len);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 16);
+        assert_eq!(u16_at(20), 1, "PCM");
+        assert_eq!(u16_at(22), CHANNELS);
+        assert_eq!(u32_at(24), SAMPLE_RATE);
+        assert_eq!(u32_at(28), SAMPLE_RATE * 4, "byte rate");
+        assert_eq!(u16_at(32), 4, "block align");
+        assert_eq!(u16_at(34), 16, "bits per sample");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40), data_len);
+        assert_eq!(bytes.len(), 44 + data_len as usize);
+    }
+}
@@ -0,0 +1,455 @@
+use crate::sketch::list::Operation;
+use std::mem::size_of;
+
+/// A checkpoint is taken every this many entries, bounding the decode work of `get`
+const CHECKPOINT_INTERVAL: usize = 64;
+
+const TAG_GET: u64 = 0;
+const TAG_SET: u64 = 1;
+const TAG_SWAP: u64 = 2;
+const TAG_GETS: u64 = 3;
+
+/// A run of ops that is encoded as a single entry
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Entry {
+    /// `count` gets of consecutive indices starting at `start`, as produced by sweeps like `zing`
+    Gets { start: u32, count: u32 },
+    Set(u32, u32),
+    Swap(u32, u32),
+}
+
+impl Entry {
+    fn len(&self) -> usize {
+        match self {
+            Entry::Gets { count, .. } => *count as usize,
+            _ => 1,
+        }
+    }
+    fn op(&self, offset: usize) -> Operation {
+        match *self {
+            Entry::Gets { start, .. } => Operation::Get(start as usize + offset),
+            Entry::Set(i, x) => Operation::Set(i as usize, x as usize),
+            Entry::Swap(i, j) => Operation::Swap(i as usize, j as usize),
+        }
+    }
+    /// The index the next entry's index is delta coded against
+    fn last_index(&self) -> u32 {
+        match *self {
+            Entry::Gets { start, count } => start + count - 1,
+            Entry::Set(i, _) | Entry::Swap(i, _) => i,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Checkpoint {
+    op_index: usize,
+    byte_offset: usize,
+    prev_index: u32,
+}
+
+/// Compact, append-only record of operations. Indices are packed to `u32` and delta coded as
+/// varints, and runs of sequential gets collapse to a single entry. Ops are read back in order with
+/// `iter`/`iter_from`, which is how playback consumes them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OpLog {
+    data: Vec<u8>,
+    checkpoints: Vec<Checkpoint>,
+    /// Ops in `data`, not counting `pending`
+    encoded_len: usize,
+    entries: usize,
+    prev_index: u32,
+    /// The last entry, kept decoded while it can still grow into a longer run of gets
+    pending: Option<Entry>,
+}
+
+impl OpLog {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.encoded_len + self.pending.map_or(0, |e| e.len())
+    }
+
+    pub(crate) fn push(&mut self, op: Operation) {
+        if let (Operation::Get(i), Some(Entry::Gets { start, count })) = (op, &mut self.pending)
+            && i == *start as usize + *count as usize
+        {
+            *count += 1;
+            return;
+        }
+        if let Some(entry) = self.pending.take() {
+            self.encode(entry);
+        }
+        let pack = |x: usize| u32::try_from(x).expect("op index too large for the op log");
+        self.pending = Some(match op {
+            Operation::Get(i) => Entry::Gets { start: pack(i), count: 1 },
+            Operation::Set(i, x) => Entry::Set(pack(i), pack(x)),
+            Operation::Swap(i, j) => Entry::Swap(pack(i), pack(j)),
+        });
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_> {
+        self.iter_from(0)
+    }
+
+    /// Iterates over the ops from `start` onwards
+    pub(crate) fn iter_from(&self, start: usize) -> Iter<'_> {
+        Iter { log: self, cursor: self.cursor_at(start) }
+    }
+
+    fn cursor_at(&self, start: usize) -> Cursor {
+        let checkpoint = match self.checkpoints.partition_point(|c| c.op_index <= start) {
+            0 => Checkpoint { op_index: 0, byte_offset: 0, prev_index: 0 },
+            n => self.checkpoints[n - 1],
+        };
+        let mut cursor = Cursor {
+            byte_offset: checkpoint.byte_offset,
+            prev_index: checkpoint.prev_index,
+            entry_start: checkpoint.op_index,
+            entry: None,
+            offset: 0,
+        };
+        cursor.load_entry(self);
+        // Skip whole entries, then into the entry containing `start`
+        while let Some(entry) = cursor.entry
+            && cursor.entry_start + entry.len() <= start
+        {
+            cursor.entry_start += entry.len();
+            cursor.load_entry(self);
+        }
+        cursor.offset = start.saturating_sub(cursor.entry_start);
+        cursor
+    }
+
+    /// The encoded entries, as stored in the binary trace format
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut log = self.clone();
+        if let Some(entry) = log.pending.take() {
+            log.encode(entry);
+        }
+        log.data
+    }
+
+    /// Decodes data written by `to_bytes`, passing every op to `validate`. Errors are reported with
+    /// the byte offset (into `data`) of the entry at fault
+    pub(crate) fn from_bytes(
+        data: &[u8],
+        mut validate: impl FnMut(Operation) -> Result<(), String>,
+    ) -> Result<OpLog, (usize, String)> {
+        let mut log = OpLog::new();
+        let mut offset = 0;
+        let mut prev_index = 0_u32;
+        while offset < data.len() {
+            let entry_offset = offset;
+            let fail = |msg: &str| (entry_offset, msg.to_string());
+            let mut next = || try_read_varint(data, &mut offset).ok_or_else(|| fail("truncated or overlong varint"));
+            let head = next()?;
+            let index = u32::try_from(prev_index as i64 + unzigzag(head >> 2)).map_err(|_| fail("index delta out of range"))?;
+            let entry = match head & 0b11 {
+                TAG_GET => Entry::Gets { start: index, count: 1 },
+                TAG_SET => Entry::Set(index, u32::try_from(next()?).map_err(|_| fail("value out of range"))?),
+                TAG_SWAP => {
+                    let j = u32::try_from(index as i64 + unzigzag(next()?)).map_err(|_| fail("index delta out of range"))?;
+                    Entry::Swap(index, j)
+                }
+                _ => {
+                    let count = u32::try_from(next()?).map_err(|_| fail("invalid run length"))?;
+                    if count == 0 || index as u64 + count as u64 > u32::MAX as u64 + 1 {
+                        return Err(fail("invalid run length"));
+                    }
+                    Entry::Gets { start: index, count }
+                }
+            };
+            for k in 0..entry.len() {
+                let op = entry.op(k);
+                validate(op).map_err(|msg| (entry_offset, msg))?;
+                log.push(op);
+            }
+            prev_index = entry.last_index();
+        }
+        Ok(log)
+    }
+
+    /// Bytes of heap used by the log
+    pub(crate) fn memory_bytes(&self) -> usize {
+        self.data.capacity() + self.checkpoints.capacity() * size_of::<Checkpoint>()
+    }
+
+    fn encode(&mut self, entry: Entry) {
+        if self.entries.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.checkpoints.push(Checkpoint {
+                op_index: self.encoded_len,
+                byte_offset: self.data.len(),
+                prev_index: self.prev_index,
+            });
+        }
+        let delta = |i: u32, prev: u32| zigzag(i as i64 - prev as i64);
+        match entry {
+            Entry::Gets { start, count: 1 } => {
+                write_varint(&mut self.data, delta(start, self.prev_index) << 2 | TAG_GET);
+            }
+            Entry::Gets { start, count } => {
+                write_varint(&mut self.data, delta(start, self.prev_index) << 2 | TAG_GETS);
+                write_varint(&mut self.data, count as u64);
+            }
+            Entry::Set(i, x) => {
+                write_varint(&mut self.data, delta(i, self.prev_index) << 2 | TAG_SET);
+                write_varint(&mut self.data, x as u64);
+            }
+            Entry::Swap(i, j) => {
+                write_varint(&mut self.data, delta(i, self.prev_index) << 2 | TAG_SWAP);
+                write_varint(&mut self.data, delta(j, i));
+            }
+        }
+        self.prev_index = entry.last_index();
+        self.encoded_len += entry.len();
+        self.entries += 1;
+    }
+
+    fn decode(&self, byte_offset: &mut usize, prev_index: u32) -> Entry {
+        let head = read_varint(&self.data, byte_offset);
+        let index = (prev_index as i64 + unzigzag(head >> 2)) as u32;
+        match head & 0b11 {
+            TAG_GET => Entry::Gets { start: index, count: 1 },
+            TAG_SET => Entry::Set(index, read_varint(&self.data, byte_offset) as u32),
+            TAG_SWAP => {
+                let j = (index as i64 + unzigzag(read_varint(&self.data, byte_offset))) as u32;
+                Entry::Swap(index, j)
+            }
+            _ => Entry::Gets { start: index, count: read_varint(&self.data, byte_offset) as u32 },
+        }
+    }
+}
+
+impl Extend<Operation> for OpLog {
+    fn extend<T: IntoIterator<Item = Operation>>(&mut self, iter: T) {
+        for op in iter {
+            self.push(op);
+        }
+    }
+}
+
+impl FromIterator<Operation> for OpLog {
+    fn from_iter<T: IntoIterator<Item = Operation>>(iter: T) -> Self {
+        let mut log = OpLog::new();
+        log.extend(iter);
+        log
+    }
+}
+
+/// Position of an iterator within a log
+#[derive(Copy, Clone, Debug)]
+struct Cursor {
+    byte_offset: usize,
+    prev_index: u32,
+    /// Op index of the first op in `entry`
+    entry_start: usize,
+    entry: Option<Entry>,
+    offset: usize,
+}
+
+impl Cursor {
+    fn load_entry(&mut self, log: &OpLog) {
+        self.entry = if self.byte_offset < log.data.len() {
+            let entry = log.decode(&mut self.byte_offset, self.prev_index);
+            self.prev_index = entry.last_index();
+            Some(entry)
+        } else if self.entry_start == log.encoded_len {
+            log.pending
+        } else {
+            None
+        };
+    }
+
+    fn next(&mut self, log: &OpLog) -> Option<Operation> {
+        loop {
+            let entry = self.entry?;
+            if self.offset < entry.len() {
+                self.offset += 1;
+                return Some(entry.op(self.offset - 1));
+            }
+            self.entry_start += entry.len();
+            self.offset = 0;
+            self.load_entry(log);
+        }
+    }
+}
+
+pub(crate) struct Iter<'a> {
+    log: &'a OpLog,
+    cursor: Cursor,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Operation;
+
+    fn next(&mut self) -> Option<Operation> {
+        self.cursor.next(self.log)
+    }
+}
+
+/// Iterates over a log it owns, for readers that outlive the log's owner
+pub(crate) struct IntoIter {
+    log: OpLog,
+    cursor: Cursor,
+}
+
+impl Iterator for IntoIter {
+    type Item = Operation;
+
+    fn next(&mut self) -> Option<Operation> {
+        self.cursor.next(&self.log)
+    }
+}
+
+impl IntoIterator for OpLog {
+    type Item = Operation;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter { cursor: self.cursor_at(0), log: self }
+    }
+}
+
+/// The most recent ops pushed, for readers that only look back so far. Ops go into `newer` until
+/// it holds `kept` of them, then `older` is dropped and replaced by it, so at least `kept` ops
+/// before the last one pushed can always be read. Ops keep the index they were pushed at
+#[derive(Debug, Clone)]
+pub(crate) struct RecentOps {
+    older: OpLog,
+    newer: OpLog,
+    /// Index of the first op in `older`
+    start: usize,
+    kept: usize,
+}
+
+impl RecentOps {
+    pub(crate) fn new(kept: usize) -> Self {
+        Self { older: OpLog::new(), newer: OpLog::new(), start: 0, kept }
+    }
+
+    /// Ops pushed so far, including those since dropped
+    pub(crate) fn len(&self) -> usize {
+        self.start + self.older.len() + self.newer.len()
+    }
+
+    /// Index of the oldest op still held
+    pub(crate) fn first(&self) -> usize {
+        self.start
+    }
+
+    pub(crate) fn push(&mut self, op: Operation) {
+        if self.newer.len() == self.kept {
+            self.start += self.older.len();
+            self.older = std::mem::take(&mut self.newer);
+        }
+        self.newer.push(op);
+    }
+
+    /// The op at `index`, which must not have been dropped yet
+    pub(crate) fn get(&self, index: usize) -> Operation {
+        self.iter_from(index).next().expect("op dropped or not pushed yet")
+    }
+
+    /// Iterates over the ops from `start` onwards, which must not have been dropped yet
+    pub(crate) fn iter_from(&self, start: usize) -> impl Iterator<Item = Operation> + '_ {
+        assert!(start >= self.start, "op {start} was dropped, the oldest held is {}", self.start);
+        let newer_start = self.start + self.older.len();
+        let older = (start < newer_start).then(|| self.older.iter_from(start - self.start));
+        older.into_iter().flatten().chain(self.newer.iter_from(start.saturating_sub(newer_start)))
+    }
+
+    /// Bytes of heap used by the ops held
+    pub(crate) fn memory_bytes(&self) -> usize {
+        self.older.memory_bytes() + self.newer.memory_bytes()
+    }
+}
+
+fn zigzag(x: i64) -> u64 {
+    ((x << 1) ^ (x >> 63)) as u64
+}
+
+fn unzigzag(x: u64) -> i64 {
+    (x >> 1) as i64 ^ -((x & 1) as i64)
+}
+
+fn write_varint(data: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        data.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    data.push(x as u8);
+}
+
+fn read_varint(data: &[u8], offset: &mut usize) -> u64 {
+    let mut x = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*offset];
+        *offset += 1;
+        x |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return x;
+        }
+        shift += 7;
+    }
+}
+
+fn try_read_varint(data: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut x = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*offset)?;
+        *offset += 1;
+        x |= ((byte & 0x7f) as u64).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(x);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_seeks() {
+        let mut ops = vec![];
+        ops.extend((0..300).map(Operation::Get));
+        ops.extend([Operation::Swap(5, 2), Operation::Get(7), Operation::Get(8), Operation::Set(0, 9)]);
+        for k in 0..500 {
+            ops.push(Operation::Get(k % 17));
+            ops.push(Operation::Swap(k % 13, 499 - k));
+            ops.push(Operation::Set(1000 - k, k));
+        }
+        ops.extend((10..20).map(Operation::Get));
+
+        let log: OpLog = ops.iter().copied().collect();
+        assert_eq!(log.len(), ops.len());
+        assert_eq!(log.iter().collect::<Vec<_>>(), ops);
+        for start in [0, 1, 299, 300, 301, 303, 304, 777, ops.len() - 5, ops.len()] {
+            assert_eq!(log.iter_from(start).collect::<Vec<_>>(), ops[start..]);
+        }
+        assert!(log.memory_bytes() < ops.len() * size_of::<Operation>() / 4);
+
+        let decoded = OpLog::from_bytes(&log.to_bytes(), |_| Ok(())).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), ops);
+    }
+
+    #[test]
+    fn recent_ops_keep_enough_to_look_back_on() {
+        let ops: Vec<_> = (0..1000).map(|k| if k % 3 == 0 { Operation::Swap(k, k + 1) } else { Operation::Get(k) }).collect();
+        let mut recent = RecentOps::new(100);
+        for (n, &op) in ops.iter().enumerate() {
+            recent.push(op);
+            assert_eq!(recent.len(), n + 1);
+            assert!(recent.first() + 100 <= n + 1 || recent.first() == 0);
+            let back = (n + 1).saturating_sub(100);
+            assert_eq!(recent.iter_from(back).collect::<Vec<_>>(), ops[back..=n]);
+            assert_eq!(recent.get(n), op);
+        }
+        assert!(recent.first() >= 800, "old ops should have been dropped");
+    }
+}
@@ -1,144 +1,356 @@
-use crate::sketch::list::Operation;
-use crate::sketch::{shuffle_step_by_step, zing, List};
-use std::collections::HashMap;
+use crate::sketch::list::{first_positions, Operation, UNKNOWN_ORIGIN};
+use crate::sketch::op_log::RecentOps;
+use crate::sketch::dataset::Dataset;
+use crate::sketch::distribution;
+use crate::sketch::is_stable;
+use crate::sketch::methods::SortMethod;
+use crate::sketch::trace::{TraceEvent, TraceStream};
+use crate::sketch::trace_io::Trace;
+use crate::sketch::activity::{Activity, ActivityModel};
 use crate::sketch::audio::{start_audio, AudioModel, AudioHandle};
+use std::collections::VecDeque;
+
+/// Played ops kept to look back on, as many as the access heatmap shows at its coarsest
+const KEPT_OPS: usize = 1 << 20;
+
+/// Conditions that pause playback once they are met
+#[derive(Debug, Default, Clone)]
+pub struct Breakpoints {
+    /// Stop after an op writes to `write_index`
+    pub on_write: bool,
+    pub write_index: usize,
+    /// Stop once `op_count` ops have been played, cleared once it is hit
+    pub on_op_count: bool,
+    pub op_count: usize,
+    /// Stop at the next phase boundary, cleared once it is hit
+    pub on_phase: bool,
+}
+
 pub struct SortPlayer {
-    starting_vec: Vec<usize>,
-    pub(crate) record_of_operations: Vec<Operation>,
+    /// The algorithm the trace comes from, `None` for an imported trace
+    pub(crate) method: Option<SortMethod>,
+    pub(crate) starting_vec: Vec<usize>,
+    /// Ops received from `trace`, of which only the last `KEPT_OPS` or so played ones are kept. A
+    /// reset starts the trace again instead of replaying from here
+    pub(crate) recent_ops: RecentOps,
+    trace: TraceStream,
+    /// Ops in the whole trace, once known
+    total_ops: Option<usize>,
+    /// Phase boundaries received and not yet played past
+    phase_boundaries: VecDeque<usize>,
     pub(crate) length: usize,
-    current_play_back_point: usize,
+    pub(crate) current_play_back_point: usize,
     pub(crate) playback_vec: Vec<usize>,
-    pub(crate) playback_rate: usize,
+    /// Where each element of `playback_vec` was when sorting started, `UNKNOWN_ORIGIN` once a
+    /// plain set has overwritten it
+    pub(crate) playback_origins: Vec<usize>,
+    /// (op index, origin) of each set received and not yet played whose origin was sent
+    set_origins: VecDeque<(usize, usize)>,
+    /// Op index at which sorting started and origins were taken
+    retag_point: Option<usize>,
+    /// Origin of a set that doesn't send one, by level, once origins have been taken
+    predicted_origins: Option<Vec<usize>>,
+    /// (op index, list index) of each compare received and not yet played
+    compares: VecDeque<(usize, usize)>,
+    pub(crate) activity: ActivityModel,
+    pub(crate) playback_rate: f64,
     pub(crate) stream: AudioHandle,
+    pub(crate) paused: bool,
+    pub(crate) breakpoints: Breakpoints,
 }
 
 impl SortPlayer {
     pub fn new(
         length: usize,
-        sort: fn(&mut List),
-        speed: usize,
+        sort: SortMethod,
+        speed: f64,
         shuffle: bool,
+        dataset: Dataset,
         list: Vec<usize>,
     ) -> Self {
-        let length_equal = length == list.len();
-
-        let input = if length_equal && contains_all(&list) {
-            list
-        } else {
-            starting(length) // refresh
-        };
-        let mut list = List::new(input.clone(), length);
-
-        if shuffle || !length_equal {
-            shuffle_step_by_step(&mut list);
-        }
+        let (input, trace) = dataset.start(sort, length, shuffle, list);
+        Self::with_trace(Some(sort), input, length, trace, None, speed)
+    }
 
-        if !list.is_sorted() {
-            sort(&mut list);
-        }
-        zing(&mut list);
+    /// Replays a previously recorded (e.g. imported) trace
+    pub fn from_trace(trace: Trace, speed: f64) -> Self {
+        let (input, length, total_ops) = (trace.starting_vec.clone(), trace.length, trace.operations.len());
+        Self::with_trace(None, input, length, TraceStream::recorded(trace), Some(total_ops), speed)
+    }
 
-        let audio_model = AudioModel { phase: 0.0, hz: 440.0, volume: 0.2 };
+    fn with_trace(
+        method: Option<SortMethod>,
+        input: Vec<usize>,
+        length: usize,
+        trace: TraceStream,
+        total_ops: Option<usize>,
+        speed: f64,
+    ) -> Self {
+        let audio_model = AudioModel { phase: 0.0, hz: 440.0, volume: 0.2, ..Default::default() };
         let stream = start_audio(audio_model).expect("failed to start audio");
 
 
         Self {
+            method,
             starting_vec: input.clone(),
-            record_of_operations: list.record_of_operations,
+            recent_ops: RecentOps::new(KEPT_OPS),
+            trace,
+            total_ops,
+            phase_boundaries: VecDeque::new(),
             length,
             playback_vec: input.clone(),
+            playback_origins: (0..length).collect(),
+            set_origins: VecDeque::new(),
+            retag_point: None,
+            predicted_origins: None,
+            compares: VecDeque::new(),
+            activity: ActivityModel::new(length),
             current_play_back_point: 0,
             playback_rate: speed,
             stream,
+            paused: false,
+            breakpoints: Breakpoints::default(),
         }
     }
 
-    fn playback_complete(&self) -> bool {
-        self.current_play_back_point == self.record_of_operations.len()
+    pub(crate) fn playback_complete(&self) -> bool {
+        self.trace.finished && self.current_play_back_point == self.recent_ops.len()
+    }
+    /// Why the sort stopped before finishing, if it did
+    pub(crate) fn trace_error(&self) -> Option<&str> {
+        self.trace.error.as_deref()
+    }
+    /// How many ops the whole trace has, once the sort has finished
+    pub(crate) fn total_ops(&self) -> Option<usize> {
+        self.total_ops
+    }
+    /// Bytes of heap held for the trace: the ops kept, events received ahead of playback and what
+    /// the trace is produced from
+    pub(crate) fn trace_memory_bytes(&self) -> usize {
+        let pending = self.phase_boundaries.capacity() * size_of::<usize>()
+            + self.set_origins.capacity() * size_of::<(usize, usize)>()
+            + self.compares.capacity() * size_of::<(usize, usize)>();
+        let predicted = self.predicted_origins.as_ref().map_or(0, Vec::capacity) * size_of::<usize>();
+        self.recent_ops.memory_bytes() + pending + predicted + self.trace.memory_bytes()
+    }
+    /// Produces the whole trace again from the start when called, e.g. to export it on a worker
+    /// thread
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn recorder(&self) -> impl FnOnce() -> Trace + Send + 'static {
+        self.trace.recorder()
+    }
+    /// The trace again from its first event, to read through without disturbing playback
+    #[cfg(target_family = "wasm")]
+    pub(crate) fn fresh_trace(&self) -> TraceStream {
+        self.trace.fresh()
+    }
+    /// Name for files exported from the trace, after the algorithm it comes from
+    pub(crate) fn export_name(&self) -> String {
+        format!("{}_{}", self.method.map_or("imported", |method| method.name()), self.length)
+    }
+    /// Makes sure the op at the playback point has been received, returning false at the end
+    fn fetch_next(&mut self) -> bool {
+        while self.current_play_back_point == self.recent_ops.len() {
+            let Some(event) = self.trace.next() else {
+                self.total_ops = Some(self.recent_ops.len());
+                return false;
+            };
+            self.receive(event);
+        }
+        true
+    }
+    fn receive(&mut self, event: TraceEvent) {
+        match event {
+            TraceEvent::Op(op) => self.recent_ops.push(op),
+            TraceEvent::Phase => {
+                let point = self.recent_ops.len();
+                if self.phase_boundaries.back() != Some(&point) {
+                    self.phase_boundaries.push_back(point);
+                }
+            }
+            TraceEvent::Origin(origin) => self.set_origins.push_back((self.recent_ops.len(), origin)),
+            TraceEvent::Retag => self.retag_point = Some(self.recent_ops.len()),
+            TraceEvent::Compare(index) => self.compares.push_back((self.recent_ops.len(), index)),
+        }
     }
     pub fn reset_play(&mut self) {
+        self.trace.restart();
+        self.recent_ops = RecentOps::new(KEPT_OPS);
+        self.phase_boundaries.clear();
+        self.set_origins.clear();
+        self.retag_point = None;
+        self.predicted_origins = None;
+        self.compares.clear();
         self.playback_vec = self.starting_vec.clone();
+        self.playback_origins = (0..self.length).collect();
+        self.activity.clear();
         self.current_play_back_point = 0;
     }
-    pub fn increment_playback(&mut self) {
-        let next_op = self.record_of_operations[self.current_play_back_point];
-        self.apply_op(next_op);
+    /// Whether equal values are in the order they were in when sorting started
+    pub(crate) fn is_stable(&self) -> bool {
+        is_stable(&self.playback_vec, &self.playback_origins)
+    }
+    /// Plays the op at the playback point, which `fetch_next` must have received
+    fn increment_playback(&mut self) -> Operation {
+        let point = self.current_play_back_point;
+        let next_op = self.recent_ops.get(point);
+        if self.retag_point == Some(point) {
+            self.playback_origins = (0..self.length).collect();
+            self.predicted_origins = Some(first_positions(&self.playback_vec));
+        }
+        self.activity.advance(point + 1);
+        // Compares made before this op, which are only received along with it
+        while let Some(&(at, index)) = self.compares.front()
+            && at <= point
+        {
+            self.activity.record(index, Activity::Compare);
+            self.compares.pop_front();
+        }
+        match next_op {
+            Operation::Get(i) => self.activity.record(i, Activity::Read),
+            Operation::Set(i, v) => {
+                let sent = self.set_origins.front().filter(|&&(p, _)| p == point).map(|&(_, origin)| origin);
+                if sent.is_some() {
+                    self.set_origins.pop_front();
+                }
+                let predicted = self.predicted_origins.as_ref().and_then(|p| p.get(v).copied());
+                self.playback_origins[i] = sent.or(predicted).unwrap_or(UNKNOWN_ORIGIN);
+                self.activity.record(i, Activity::Write);
+            }
+            Operation::Swap(i, j) => {
+                self.playback_origins.swap(i, j);
+                self.activity.record(i, Activity::Swap);
+                self.activity.record(j, Activity::Swap);
+            }
+        }
+        apply_op(&mut self.playback_vec, next_op);
         self.current_play_back_point += 1;
-        let v = match next_op {
-            Operation::Get(i) => self.playback_vec[i],
-            Operation::Set(i, v) => v,
-            Operation::Swap(i, j) => self.playback_vec[i],
-        };
-        let x = lerp(120.0, 1212.0, v as f64 / self.length as f64);
+        while self.phase_boundaries.front().is_some_and(|&p| p < self.current_play_back_point) {
+            self.phase_boundaries.pop_front();
+        }
+        let (x, index_prop) = note(next_op, &self.playback_vec, self.length);
 
         self.stream.play().unwrap();
 
         self.stream
             .send(move |audio| {
                 audio.hz = x;
+                audio.pan = audio.pan_mode.pan(index_prop);
             })
             .unwrap();
+        next_op
     }
-    pub fn play(&mut self, x: usize) {
+    /// Plays up to `x` ops, returning how many were actually played. Stops early if paused or a
+    /// breakpoint is hit
+    pub fn play(&mut self, x: usize) -> usize {
+        let mut played = 0;
         for _ in 0..x {
-            if !self.playback_complete() {
-                self.increment_playback();
-            } else {
+            if self.paused || !self.fetch_next() {
                 self.stream.pause().unwrap();
+                break;
             }
+            let op = self.increment_playback();
+            played += 1;
+            if self.breakpoint_hit(op) {
+                self.paused = true;
+            }
+        }
+        if self.playback_complete() {
+            self.activity.clear();
         }
+        played
+    }
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
     }
-    pub(crate) fn most_recent_gets(&self) -> Option<HashMap<usize, f32>> {
-        let history_dist = (self.length / 20).max(1).min(self.current_play_back_point);
-        if self.playback_complete() || self.current_play_back_point == 0 {
-            return None;
+    /// Plays a single op regardless of pause
+    pub fn step(&mut self) {
+        self.paused = true;
+        if self.fetch_next() {
+            self.increment_playback();
         }
-        let mut map = HashMap::new();
-        for j in 0..history_dist {
-            let i = self.current_play_back_point - j - 1;
-            let prop = if history_dist == 1 {
-                1.0
-            } else {
-                1.0 - (j as f32 / (history_dist - 1) as f32)
-            };
-            if let Operation::Get(a) = self.record_of_operations[i] {
-                map.entry(a)
-                    .and_modify(|x: &mut f32| *x = x.max(prop))
-                    .or_insert(prop);
+    }
+    /// Plays ops until one that writes to the list (a set or a swap) has been played
+    pub fn step_to_next_write(&mut self) {
+        self.paused = true;
+        while self.fetch_next() {
+            if is_write(self.increment_playback()) {
+                break;
             }
         }
-        Some(map)
     }
-    fn apply_op(&mut self, op: Operation) {
-        match op {
-            Operation::Get(_x) => {}
-            Operation::Set(i, x) => {
-                self.playback_vec[i] = x;
-            }
-            Operation::Swap(a, b) => {
-                self.playback_vec.swap(a, b);
-            }
+    /// Resumes playback until the start of the next phase
+    pub fn play_to_next_phase(&mut self) {
+        self.breakpoints.on_phase = true;
+        self.paused = false;
+    }
+    fn breakpoint_hit(&mut self, op: Operation) -> bool {
+        // A phase boundary here is only received along with the op that follows it
+        self.fetch_next();
+        let b = &mut self.breakpoints;
+        let point = self.current_play_back_point;
+        let write_hit = b.on_write && match op {
+            Operation::Get(_) => false,
+            Operation::Set(i, _) => i == b.write_index,
+            Operation::Swap(i, j) => i == b.write_index || j == b.write_index,
+        };
+        // At or past the count, in case it was set behind the playback point or play jumped over it
+        let count_hit = b.on_op_count && point >= b.op_count;
+        if count_hit {
+            b.on_op_count = false;
+        }
+        let phase_hit = b.on_phase && self.phase_boundaries.front() == Some(&point);
+        if phase_hit {
+            b.on_phase = false;
         }
+        write_hit || count_hit || phase_hit
     }
-    pub(crate) fn sv(&self, q: f32) -> (f32, f32) {
-        let v = 0.5 + 0.5 * q;
-        let s = 0.8 - 0.2 * q;
-        (s, v)
+}
+
+pub(crate) fn is_write(op: Operation) -> bool {
+    !matches!(op, Operation::Get(_))
+}
+
+pub(crate) fn apply_op(vec: &mut [usize], op: Operation) {
+    match op {
+        Operation::Get(_x) => {}
+        Operation::Set(i, x) => {
+            vec[i] = x;
+        }
+        Operation::Swap(a, b) => {
+            vec.swap(a, b);
+        }
     }
 }
 
-pub fn starting(length: usize) -> Vec<usize> {
-    let v = (0..length).into_iter().map(|x| x).collect::<Vec<usize>>();
+/// Pitch (hz) and index proportion of the note for `op`, once it has been applied to `vec`
+pub(crate) fn note(op: Operation, vec: &[usize], length: usize) -> (f64, f64) {
+    let (i, v) = match op {
+        Operation::Get(i) => (i, vec[i]),
+        Operation::Set(i, v) => (i, v),
+        Operation::Swap(i, _j) => (i, vec[i]),
+    };
+    let hz = lerp(120.0, 1212.0, v as f64 / length as f64);
+    (hz, i as f64 / length as f64)
+}
+
+/// Sorted values to start from. With `few_unique` there are only eight distinct values, so equal
+/// keys show whether an algorithm is stable
+pub fn starting(length: usize, few_unique: bool) -> Vec<usize> {
     // v.shuffle(&mut thread_rng());
-    v
+    if few_unique {
+        return distribution::few_unique(length);
+    }
+    (0..length).collect()
 }
 
 pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
     a + (b - a) * t
 }
 
-pub fn contains_all(l: &Vec<usize>) -> bool {
-    let mut v = l.clone();
+/// Whether `l` holds the same values as the sorted `values`
+pub fn is_permutation_of(l: &[usize], values: &[usize]) -> bool {
+    let mut v = l.to_owned();
     v.sort();
-    v.windows(2).all(|x| x[0] == x[1] - 1)
+    v == values
 }
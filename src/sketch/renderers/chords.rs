@@ -0,0 +1,78 @@
+use std::f32::consts::{PI, TAU};
+use nannou::Draw;
+use nannou::color::gray;
+use nannou::geom::Vec2;
+use nannou_egui::egui::{self, Slider};
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::disparity_dots::radius;
+use crate::sketch::renderers::mesh::{ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
+
+/// Points along each chord's curve
+const CHORD_RESOLUTION: usize = 16;
+
+/// Indices around a circle, with an arc from each element's index to the index it belongs at.
+/// Arcs bow further towards the centre the further the element is from home, and vanish once
+/// it arrives
+pub struct Chords {
+    palette: Palette,
+    weight: f32,
+    mesh: ElementMesh<(Palette, f32, f32)>,
+}
+
+impl Default for Chords {
+    fn default() -> Self {
+        Self { palette: Palette::default(), weight: 1.0, mesh: ElementMesh::new(Shape::strip(CHORD_RESOLUTION + 1)) }
+    }
+}
+
+impl Renderer for Chords {
+    fn name(&self) -> &'static str {
+        "Displacement chords"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, aspect: f32) {
+        let draw = draw.scale(0.5).xy(Vec2::splat(1.0));
+        let rim = (0..=128).map(|k| on_circle(k as f32 / 128.0 * TAU, 1.0, aspect));
+        draw.polyline().weight(0.002).points(rim).color(gray(0.3));
+        self.mesh.draw(&draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ui.add(Slider::new(&mut self.weight, 0.25..=8.0).logarithmic(true).text("Line weight"));
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let half_weight = 0.0015 * self.weight;
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, self.weight, aspect), |i, points| {
+            let x = player.playback_vec[i];
+            let colour = palette.colour((x + 1) as f32 / length, RESTING.0, RESTING.1);
+            let target = x.min(player.length - 1);
+            if target == i {
+                // Nothing to draw, so every vertex in the same place
+                points.fill(Vec2::ZERO);
+                return colour;
+            }
+            let from = i as f32 / length * TAU;
+            // The shorter way round to the target
+            let delta = ((target as f32 - i as f32) / length * TAU + PI).rem_euclid(TAU) - PI;
+            let (start, end) = (on_circle(from, 1.0, aspect), on_circle(from + delta, 1.0, aspect));
+            let control = on_circle(from + delta / 2.0, radius(i, target, length), aspect);
+            for (k, pair) in points.chunks_exact_mut(2).enumerate() {
+                let t = k as f32 / CHORD_RESOLUTION as f32;
+                let point = start * (1.0 - t) * (1.0 - t) + control * 2.0 * t * (1.0 - t) + end * t * t;
+                let tangent = (start - control) * (1.0 - t) + (control - end) * t;
+                let across = tangent.perp().normalize_or_zero() * half_weight;
+                pair.copy_from_slice(&[point - across, point + across]);
+            }
+            colour
+        });
+    }
+}
+
+fn on_circle(angle: f32, r: f32, aspect: f32) -> Vec2 {
+    Vec2::new(angle.cos() / aspect, angle.sin()) * r * 0.9
+}
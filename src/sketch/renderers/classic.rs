@@ -1,22 +1,49 @@
 use nannou::Draw;
-use nannou::draw::primitive::polygon::PolygonOptions;
 use nannou::geom::Vec2;
+use nannou_egui::egui;
 use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
 
-pub fn draw_state(player: &SortPlayer, draw: &Draw, _aspect: f32) {
-    let length = player.length as f32;
-    let fudge_factor = length;
-    let draw = draw.scale(1.0 / fudge_factor);
-
-    let gets = player.most_recent_gets();
-    for (i, &x) in player.playback_vec.iter().enumerate() {
-        let height = x as f32 / length;
-        let width = 1.0 / length;
-        let offset_x = i as f32 / length;
-        let points = four_corners_trap(Vec2::new(offset_x, 0.0), Vec2::new(width, height), 1.0 / length);
-        let q = gets.as_ref().map(|x| x.get(&i).cloned()).flatten().unwrap_or(0.);
-        let (s, v) = player.sv(q);
-        draw.polygon().points(points.into_iter().map(|x| x * fudge_factor).collect::<Vec<_>>()).hsv(height, s, v);
+/// A bar per element, as tall as its value
+pub struct Classic {
+    palette: Palette,
+    /// Slope the top of each bar up to the next value, so a sorted list looks like a ramp
+    slanted: bool,
+    mesh: ElementMesh<(Palette, bool)>,
+}
+
+impl Default for Classic {
+    fn default() -> Self {
+        Self { palette: Palette::default(), slanted: true, mesh: ElementMesh::new(Shape::quads(1)) }
+    }
+}
+
+impl Renderer for Classic {
+    fn name(&self) -> &'static str {
+        "Classic"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ui.checkbox(&mut self.slanted, "Slanted tops");
+    }
+
+    fn update(&mut self, player: &SortPlayer, _aspect: f32) {
+        let length = player.length as f32;
+        let dh = if self.slanted { 1.0 / length } else { 0.0 };
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, self.slanted), |i, points| {
+            let height = player.playback_vec[i] as f32 / length;
+            let width = 1.0 / length;
+            let offset_x = i as f32 / length;
+            points.copy_from_slice(&four_corners_trap(Vec2::new(offset_x, 0.0), Vec2::new(width, height), dh));
+            palette.colour(height, RESTING.0, RESTING.1)
+        });
     }
 }
 
@@ -33,9 +60,9 @@ fn _four_corners_rect(bottom_left: Vec2, wh: Vec2) -> [Vec2; 4] {
     [bottom_left, bottom_right, top_right, top_left]
 }
 
-fn four_corners_trap(bottom_left: Vec2, wh: Vec2, dh: f32) -> Vec<Vec2> {
+fn four_corners_trap(bottom_left: Vec2, wh: Vec2, dh: f32) -> [Vec2; 4] {
     let top_right = bottom_left + wh + Vec2::new(0.0, dh);
     let bottom_right = bottom_left + wh * Vec2::X;
     let top_left = bottom_left + wh * Vec2::Y;
-    vec![bottom_left, bottom_right, top_right, top_left]
+    [bottom_left, bottom_right, top_right, top_left]
 }
@@ -1,27 +1,51 @@
 use std::f32::consts::TAU;
 use nannou::Draw;
 use nannou::geom::Vec2;
+use nannou_egui::egui;
 use crate::sketch::player::SortPlayer;
 use crate::sketch::renderers::disparity_dots::radius;
+use crate::sketch::renderers::mesh::{ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
 
-pub fn draw_state(player: &SortPlayer, draw: &Draw, aspect: f32) {
-    let draw = draw.scale(0.5).xy(Vec2::splat(1.0));
+/// The colour circle with each wedge cut short by its element's distance from its sorted position
+pub struct ColourTowers {
+    palette: Palette,
+    mesh: ElementMesh<(Palette, f32)>,
+}
+
+impl Default for ColourTowers {
+    fn default() -> Self {
+        Self { palette: Palette::default(), mesh: ElementMesh::new(Shape::polygon(3)) }
+    }
+}
+
+impl Renderer for ColourTowers {
+    fn name(&self) -> &'static str {
+        "Colour towers"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(&draw.scale(0.5).xy(Vec2::splat(1.0)));
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+    }
 
-    let length = player.length as f32;
-    let gets = player.most_recent_gets();
-    for (i, x) in player.playback_vec.iter().enumerate() {
-        let height = (x + 1) as f32 / length;
-        let i_prop = (i as f32 / length) * TAU;
-        let i_next_prop = ((i as f32 + 1.0) / length) * TAU;
-        // let dot_rad = 1.0 / length * 2.0;
-        let radius = radius(i, *x, length);
-        // let radius = 1.0;
-        let pos = Vec2::new(i_prop.cos() / aspect, i_prop.sin()) * radius * 0.9;
-        let pos_next = Vec2::new(i_next_prop.cos() / aspect, i_next_prop.sin()) * radius * 0.9;
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, aspect), |i, points| {
+            let x = player.playback_vec[i];
+            let height = (x + 1) as f32 / length;
+            let i_prop = (i as f32 / length) * TAU;
+            let i_next_prop = ((i as f32 + 1.0) / length) * TAU;
+            let radius = radius(i, x, length);
+            let pos = Vec2::new(i_prop.cos() / aspect, i_prop.sin()) * radius * 0.9;
+            let pos_next = Vec2::new(i_next_prop.cos() / aspect, i_next_prop.sin()) * radius * 0.9;
 
-        let q = gets.as_ref().map(|x| x.get(&i).cloned()).flatten().unwrap_or(0.);
-        let (s, v) = player.sv(q);
-        let (h, s, v) = (height, s, v);
-        draw.polygon().points([pos, pos_next, Vec2::ZERO]).hsv(h, s, v);
+            points.copy_from_slice(&[pos, pos_next, Vec2::ZERO]);
+            palette.colour(height, RESTING.0, RESTING.1)
+        });
     }
 }
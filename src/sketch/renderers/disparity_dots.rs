@@ -1,24 +1,54 @@
 use std::f32::consts::TAU;
 use nannou::Draw;
 use nannou::geom::Vec2;
+use nannou_egui::egui::{self, Slider};
 use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{ellipse, ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
 
+/// Sides of the polygon each dot is drawn as
+const DOT_SIDES: usize = 12;
 
-pub fn draw_state(player: &SortPlayer, draw: &Draw, aspect: f32) {
-    let draw = draw.scale(0.5).xy(Vec2::splat(1.0));
+/// A dot per element around a circle, pulled towards the centre the further it is from its
+/// sorted position
+pub struct DisparityDots {
+    palette: Palette,
+    dot_scale: f32,
+    mesh: ElementMesh<(Palette, f32, f32)>,
+}
+
+impl Default for DisparityDots {
+    fn default() -> Self {
+        Self { palette: Palette::default(), dot_scale: 1.0, mesh: ElementMesh::new(Shape::polygon(DOT_SIDES)) }
+    }
+}
+
+impl Renderer for DisparityDots {
+    fn name(&self) -> &'static str {
+        "Disparity dots"
+    }
 
-    let length = player.length as f32;
-    let gets = player.most_recent_gets();
-    for (i, x) in player.playback_vec.iter().enumerate() {
-        let height = (x + 1) as f32 / length;
-        let i_prop = (i as f32 / length) * TAU;
-        let dot_rad = (1.0 / length * 2.0).max(0.005);
-        let pos = Vec2::new(i_prop.cos() / aspect, i_prop.sin()) * radius(i, *x, length) * 0.9;
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(&draw.scale(0.5).xy(Vec2::splat(1.0)));
+    }
 
-        let q = gets.as_ref().map(|x| x.get(&i).cloned()).flatten().unwrap_or(0.);
-        let (s, v) = player.sv(q);
-        let (h, s, v) = (height, s, v);
-        draw.ellipse().resolution(100.).xy(pos).wh(Vec2::new(dot_rad, dot_rad * aspect)).hsv(h, s, v);
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ui.add(Slider::new(&mut self.dot_scale, 0.25..=8.0).logarithmic(true).text("Dot size"));
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let dot_rad = (1.0 / length * 2.0).max(0.005) * self.dot_scale;
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, self.dot_scale, aspect), |i, points| {
+            let x = player.playback_vec[i];
+            let height = (x + 1) as f32 / length;
+            let i_prop = (i as f32 / length) * TAU;
+            let pos = Vec2::new(i_prop.cos() / aspect, i_prop.sin()) * radius(i, x, length) * 0.9;
+            ellipse(points, pos, Vec2::new(dot_rad, dot_rad * aspect) / 2.0);
+            palette.colour(height, RESTING.0, RESTING.1)
+        });
     }
 }
 
@@ -27,6 +57,5 @@ pub(crate) fn radius(index: usize, value: usize, length: f32) -> f32 {
     let v_prop = value as f32 / length;
     let diff = (i_prop - v_prop).abs();
     let act_diff = diff.min(1.0 - diff);
-    let radius = 1.0 - act_diff * 2.0;
-    radius
-}
\ No newline at end of file
+    1.0 - act_diff * 2.0
+}
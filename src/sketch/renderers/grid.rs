@@ -0,0 +1,115 @@
+use nannou::Draw;
+use nannou::geom::Vec2;
+use nannou_egui::egui::{self, ComboBox};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{rect, ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
+
+/// Cells narrower than this many points are left unlabelled
+const MIN_LABELLED_CELL: f32 = 24.0;
+
+/// Elements laid out row-major on a square grid, top left first, each cell coloured by value
+pub struct Grid {
+    palette: Palette,
+    labels: Labels,
+    /// Window size in points, for sizing labels
+    window: Vec2,
+    mesh: ElementMesh<(Palette, f32)>,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self { palette: Palette::default(), labels: Labels::Value, window: Vec2::ZERO, mesh: ElementMesh::new(Shape::quads(1)) }
+    }
+}
+
+/// What to write in each cell, when cells are big enough
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+enum Labels {
+    None,
+    Value,
+    Index,
+}
+
+impl Renderer for Grid {
+    fn name(&self) -> &'static str {
+        "Grid"
+    }
+
+    fn draw(&self, player: &SortPlayer, draw: &Draw, aspect: f32) {
+        self.mesh.draw(draw);
+
+        let layout = Layout::new(player.length, aspect);
+        let cell_points = layout.cell * self.window;
+        if self.labels == Labels::None || cell_points.min_element() < MIN_LABELLED_CELL {
+            return;
+        }
+        for (i, &x) in player.playback_vec.iter().enumerate() {
+            let label = match self.labels {
+                Labels::Value => x.to_string(),
+                _ => i.to_string(),
+            };
+            // Undo the window scaling so text is laid out in points
+            draw.xy(layout.centre(i))
+                .scale_x(1.0 / self.window.x)
+                .scale_y(1.0 / self.window.y)
+                .text(&label)
+                .wh(cell_points)
+                .font_size((cell_points.min_element() / 3.0) as u32)
+                .color(nannou::color::WHITE);
+        }
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ComboBox::from_label("Labels")
+            .selected_text(format!("{:?}", self.labels))
+            .show_ui(ui, |ui| {
+                for option in Labels::iter() {
+                    ui.selectable_value(&mut self.labels, option, format!("{option:?}"));
+                }
+            });
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let layout = Layout::new(player.length, aspect);
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, aspect), |i, points| {
+            rect(points, layout.centre(i), layout.cell * 0.95);
+            palette.colour(player.playback_vec[i] as f32 / length, RESTING.0, RESTING.1)
+        });
+    }
+
+    fn on_resize(&mut self, width: f32, height: f32) {
+        self.window = Vec2::new(width, height);
+    }
+}
+
+/// Where the cells go for a length and window shape
+struct Layout {
+    columns: usize,
+    rows: usize,
+    /// Bottom left of the grid
+    corner: Vec2,
+    cell: Vec2,
+}
+
+impl Layout {
+    fn new(length: usize, aspect: f32) -> Self {
+        let columns = ((length as f32).sqrt().ceil() as usize).max(1);
+        let rows = length.div_ceil(columns).max(1);
+        // Square cells in the middle of the window
+        let fit = (aspect * rows as f32 / columns as f32).min(1.0);
+        let area = Vec2::new(fit / aspect * columns as f32 / rows as f32, fit);
+        let cell = area / Vec2::new(columns as f32, rows as f32);
+        Self { columns, rows, corner: (Vec2::ONE - area) / 2.0, cell }
+    }
+
+    fn centre(&self, i: usize) -> Vec2 {
+        let (row, column) = (i / self.columns, i % self.columns);
+        self.corner + Vec2::new(column as f32 + 0.5, (self.rows - 1 - row) as f32 + 0.5) * self.cell
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use nannou::Draw;
+use nannou::color::Srgb;
+use nannou::geom::Vec2;
+use nannou_egui::egui::{self, Slider};
+use crate::sketch::activity::Activity;
+use crate::sketch::list::Operation;
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::{Quads, Renderer};
+
+/// Columns of history kept on screen, oldest on the left
+const HISTORY: usize = 256;
+/// Most rows indices are binned into
+const MAX_ROWS: usize = 256;
+
+/// Kinds of op counted in each cell, in the order of `Cell`
+const KINDS: [Activity; 3] = [Activity::Read, Activity::Swap, Activity::Write];
+
+/// Ops of each kind in `KINDS` that touched a bin of indices
+type Cell = [u32; 3];
+
+/// Which indices were read, swapped and written over time, like a memory access trace. Each
+/// column covers a run of ops, scrolling left as playback advances
+pub struct Heatmap {
+    ops_per_column: usize,
+    /// Quads of the finished columns on screen, oldest first. Each is a unit square at
+    /// (column number, row), so scrolling only moves the mesh
+    columns: VecDeque<Quads>,
+    /// Columns finished since the counts were cleared, which is the number of the current column
+    finished: usize,
+    /// The column ops are currently being added to, and its quads
+    current: Vec<Cell>,
+    current_quads: Quads,
+    ops_in_current: usize,
+    /// Ops up to here have been counted
+    counted: usize,
+    /// Length and ops per column the counts are for
+    counted_for: (usize, usize),
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self {
+            ops_per_column: 4,
+            columns: VecDeque::new(),
+            finished: 0,
+            current: vec![],
+            current_quads: Quads::default(),
+            ops_in_current: 0,
+            counted: 0,
+            counted_for: (0, 0),
+        }
+    }
+}
+
+impl Heatmap {
+    /// Forgets the counts, and sets up to recount the ops that will be on screen at `point`
+    fn clear(&mut self, length: usize, point: usize) {
+        let rows = length.clamp(1, MAX_ROWS);
+        self.columns.clear();
+        self.finished = 0;
+        self.current = vec![[0; 3]; rows];
+        self.current_quads = Quads::default();
+        self.ops_in_current = 0;
+        let window = HISTORY * self.ops_per_column;
+        self.counted = point.saturating_sub(window) / self.ops_per_column * self.ops_per_column;
+        self.counted_for = (length, self.ops_per_column);
+    }
+
+    fn count(&mut self, op: Operation, length: usize) {
+        let rows = self.current.len();
+        let mut touch = |index: usize, kind: usize| self.current[index * rows / length.max(1)][kind] += 1;
+        match op {
+            Operation::Get(i) => touch(i, 0),
+            Operation::Swap(i, j) => {
+                touch(i, 1);
+                touch(j, 1);
+            }
+            Operation::Set(i, _) => touch(i, 2),
+        }
+        self.ops_in_current += 1;
+        if self.ops_in_current == self.ops_per_column {
+            let finished = std::mem::replace(&mut self.current, vec![[0; 3]; rows]);
+            self.columns.push_back(self.quads(&finished, self.finished));
+            if self.columns.len() > HISTORY {
+                self.columns.pop_front();
+            }
+            self.finished += 1;
+            self.ops_in_current = 0;
+        }
+    }
+
+    /// A quad for each cell of `column` with any ops, at x = `number`
+    fn quads(&self, column: &[Cell], number: usize) -> Quads {
+        let full = self.ops_per_column as f32;
+        let mut quads = Quads::default();
+        for (y, counts) in column.iter().enumerate() {
+            let Some(kind) = (0..KINDS.len()).filter(|&k| counts[k] > 0).max_by_key(|&k| counts[k]) else {
+                continue;
+            };
+            let total: u32 = counts.iter().sum();
+            let brightness = 0.35 + 0.65 * (total as f32 / full).min(1.0);
+            let (r, g, b) = KINDS[kind].colour();
+            quads.push(Vec2::new(number as f32, y as f32), Vec2::ONE, Srgb::new(r * brightness, g * brightness, b * brightness));
+        }
+        quads
+    }
+}
+
+impl Renderer for Heatmap {
+    fn name(&self) -> &'static str {
+        "Access heatmap"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        let rows = self.current.len().max(1);
+        // Current column at the right edge, so history scrolls left
+        let draw = draw
+            .scale_x(1.0 / (HISTORY + 1) as f32)
+            .scale_y(1.0 / rows as f32)
+            .x(HISTORY as f32 - self.finished as f32);
+        Quads::draw_all(self.columns.iter().chain([&self.current_quads]), &draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.add(Slider::new(&mut self.ops_per_column, 1..=4096).logarithmic(true).text("Ops per column"));
+    }
+
+    fn update(&mut self, player: &SortPlayer, _aspect: f32) {
+        let point = player.current_play_back_point;
+        // Recount from scratch after seeking back, or skipping ahead further than the screen shows
+        let skipped = point - point.min(self.counted) > HISTORY * self.ops_per_column;
+        if point < self.counted || skipped || self.counted_for != (player.length, self.ops_per_column) {
+            self.clear(player.length, point);
+        }
+        for op in player.recent_ops.iter_from(self.counted).take(point - self.counted) {
+            self.count(op, player.length);
+        }
+        self.counted = point;
+        self.current_quads = self.quads(&self.current, self.finished);
+    }
+
+    fn on_restart(&mut self, player: &SortPlayer) {
+        self.clear(player.length, 0);
+    }
+}
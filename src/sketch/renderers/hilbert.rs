@@ -0,0 +1,102 @@
+use nannou::Draw;
+use nannou::geom::Vec2;
+use nannou_egui::egui;
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{rect, ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
+
+/// A cell per element along a Hilbert curve, coloured by value. Neighbouring indices stay close
+/// together, so sorted runs show up as patches even at lengths too large for bars
+pub struct Hilbert {
+    palette: Palette,
+    mesh: ElementMesh<(Palette, f32)>,
+}
+
+impl Default for Hilbert {
+    fn default() -> Self {
+        Self { palette: Palette::default(), mesh: ElementMesh::new(Shape::quads(1)) }
+    }
+}
+
+impl Renderer for Hilbert {
+    fn name(&self) -> &'static str {
+        "Hilbert curve"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let side = side(player.length);
+        // Square cells in the middle of the window
+        let square = Vec2::new((1.0 / aspect).min(1.0), aspect.min(1.0));
+        let corner = (Vec2::ONE - square) / 2.0;
+        let cell = square / side as f32;
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, aspect), |i, points| {
+            let (cx, cy) = position(side, i);
+            rect(points, corner + Vec2::new(cx as f32 + 0.5, cy as f32 + 0.5) * cell, cell);
+            palette.colour(player.playback_vec[i] as f32 / length, RESTING.0, RESTING.1)
+        });
+    }
+}
+
+/// Side of the smallest power of two square with room for `length` cells
+fn side(length: usize) -> usize {
+    let mut side = 1;
+    while side * side < length {
+        side *= 2;
+    }
+    side
+}
+
+/// Cell of the `d`th point along the Hilbert curve filling a `side` by `side` square
+fn position(side: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_cells_are_neighbours() {
+        for length in [1, 2, 4, 8, 32, 256, 1024] {
+            let side = side(length);
+            let cells: Vec<_> = (0..length).map(|d| position(side, d)).collect();
+            assert!(cells.iter().all(|&(x, y)| x < side && y < side));
+            for pair in cells.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1, "length {length}: {pair:?}");
+            }
+            let mut unique = cells.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), length);
+        }
+    }
+}
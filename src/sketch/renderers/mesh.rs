@@ -0,0 +1,161 @@
+use nannou::Draw;
+use nannou::color::{Hsv, Srgb};
+use nannou::geom::{Vec2, Vec3};
+use crate::sketch::list::UNKNOWN_ORIGIN;
+use crate::sketch::player::SortPlayer;
+
+/// Vertices and triangles making up each element of an `ElementMesh`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    vertices: usize,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl Shape {
+    /// `count` separate quads, each given by its corners in order around it
+    pub fn quads(count: usize) -> Self {
+        let triangles = (0..count).flat_map(|q| [[0, 1, 2], [0, 2, 3]].map(|t| t.map(|v| q * 4 + v))).collect();
+        Self { vertices: count * 4, triangles }
+    }
+    /// A convex polygon given by its corners in order around it
+    pub fn polygon(sides: usize) -> Self {
+        Self { vertices: sides, triangles: (1..sides.saturating_sub(1)).map(|v| [0, v, v + 1]).collect() }
+    }
+    /// A strip given by `pairs` pairs of vertices, each pair facing each other across it
+    pub fn strip(pairs: usize) -> Self {
+        let triangles = (0..pairs.saturating_sub(1)).flat_map(|p| [[2 * p, 2 * p + 1, 2 * p + 3], [2 * p, 2 * p + 3, 2 * p + 2]]).collect();
+        Self { vertices: pairs * 2, triangles }
+    }
+}
+
+/// Fills `points` with the corners of a rectangle, for `Shape::quads(1)`
+pub fn rect(points: &mut [Vec2], centre: Vec2, size: Vec2) {
+    let half = size / 2.0;
+    points.copy_from_slice(&[centre - half, centre + Vec2::new(half.x, -half.y), centre + half, centre + Vec2::new(-half.x, half.y)]);
+}
+
+/// Fills `points` with the corners of a polygon approximating an ellipse
+pub fn ellipse(points: &mut [Vec2], centre: Vec2, radii: Vec2) {
+    let sides = points.len() as f32;
+    for (k, point) in points.iter_mut().enumerate() {
+        let angle = k as f32 / sides * std::f32::consts::TAU;
+        *point = centre + Vec2::new(angle.cos(), angle.sin()) * radii;
+    }
+}
+
+/// Every element's triangles, kept from frame to frame and drawn as a single mesh. Only elements
+/// whose value, origin or highlight changed since the last frame are rebuilt, unless the settings
+/// in `K` change
+pub struct ElementMesh<K> {
+    shape: Shape,
+    points: Vec<(Vec3, Srgb)>,
+    indices: Vec<usize>,
+    /// Value and origin each element was last built with
+    built: Vec<(usize, usize)>,
+    /// Elements that were highlighted when last built, which need rebuilding as the highlight fades
+    tinted: Vec<usize>,
+    /// Settings everything was last built with
+    key: Option<K>,
+    /// One element's vertices and their colours, filled in by the renderer
+    scratch: Vec<Vec2>,
+    scratch_colours: Vec<Hsv>,
+}
+
+impl<K: PartialEq> ElementMesh<K> {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            points: vec![],
+            indices: vec![],
+            built: vec![],
+            tinted: vec![],
+            key: None,
+            scratch: vec![],
+            scratch_colours: vec![],
+        }
+    }
+
+    /// Brings the mesh up to date with `player`. `element` fills in the vertices of the element at
+    /// an index and returns its colour before highlighting
+    pub fn update(&mut self, player: &SortPlayer, key: K, mut element: impl FnMut(usize, &mut [Vec2]) -> Hsv) {
+        self.update_shaded(player, key, |i, points, colours| colours.fill(element(i, points)));
+    }
+
+    /// Like `update`, for elements with a colour per vertex. `element` fills in both
+    pub fn update_shaded(&mut self, player: &SortPlayer, key: K, mut element: impl FnMut(usize, &mut [Vec2], &mut [Hsv])) {
+        let length = player.playback_vec.len();
+        if self.key.as_ref() != Some(&key) || self.built.len() != length {
+            let (vertices, triangles) = (self.shape.vertices, &self.shape.triangles);
+            self.points = vec![(Vec3::ZERO, Srgb::new(0.0, 0.0, 0.0)); length * vertices];
+            self.indices = (0..length).flat_map(|i| triangles.iter().flatten().map(move |v| i * vertices + v)).collect();
+            self.built = vec![(usize::MAX, usize::MAX); length];
+            self.scratch = vec![Vec2::ZERO; vertices];
+            self.scratch_colours = vec![Hsv::new(0.0, 0.0, 0.0); vertices];
+            self.key = Some(key);
+            for i in 0..length {
+                self.build(i, player, &mut element);
+            }
+        } else {
+            for i in 0..length {
+                if self.built[i] != state(player, i) {
+                    self.build(i, player, &mut element);
+                }
+            }
+            for i in std::mem::take(&mut self.tinted) {
+                self.build(i, player, &mut element);
+            }
+        }
+        // Elements highlighted now, most of which were already rebuilt above
+        self.tinted = player.activity.active().filter(|&i| i < length).collect();
+        self.tinted.sort_unstable();
+        self.tinted.dedup();
+        for i in self.tinted.clone() {
+            self.build(i, player, &mut element);
+        }
+    }
+
+    fn build(&mut self, i: usize, player: &SortPlayer, element: &mut impl FnMut(usize, &mut [Vec2], &mut [Hsv])) {
+        element(i, &mut self.scratch, &mut self.scratch_colours);
+        let vertices = self.shape.vertices;
+        let mut tinted: Option<(Hsv, Srgb)> = None;
+        let built = self.points[i * vertices..(i + 1) * vertices].iter_mut();
+        for ((point, &position), &base) in built.zip(&self.scratch).zip(&self.scratch_colours) {
+            // Vertices usually share a colour, so only tint when it changes
+            let colour = match tinted {
+                Some((previous, colour)) if previous == base => colour,
+                _ => player.activity.tint(i, base),
+            };
+            tinted = Some((base, colour));
+            *point = (position.extend(0.0), colour);
+        }
+        self.built[i] = state(player, i);
+    }
+
+    pub fn draw(&self, draw: &Draw) {
+        if !self.indices.is_empty() {
+            draw.mesh().indexed_colored(self.points.iter().copied(), self.indices.iter().copied());
+        }
+    }
+}
+
+/// What an element is built from, besides the settings
+fn state(player: &SortPlayer, i: usize) -> (usize, usize) {
+    (player.playback_vec[i], player.playback_origins.get(i).copied().unwrap_or(UNKNOWN_ORIGIN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_only_refer_to_their_own_vertices() {
+        for (shape, triangles) in [(Shape::quads(3), 6), (Shape::polygon(12), 10), (Shape::strip(17), 32)] {
+            assert_eq!(shape.triangles.len(), triangles);
+            assert!(shape.triangles.iter().flatten().all(|&v| v < shape.vertices));
+            let mut used: Vec<usize> = shape.triangles.iter().flatten().copied().collect();
+            used.sort_unstable();
+            used.dedup();
+            assert_eq!(used.len(), shape.vertices);
+        }
+    }
+}
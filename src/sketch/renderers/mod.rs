@@ -1,4 +1,184 @@
 pub(crate) mod classic;
 pub(crate) mod disparity_dots;
 pub(crate) mod colour_circle;
-pub(crate) mod colour_towers;
\ No newline at end of file
+pub(crate) mod colour_towers;
+pub(crate) mod stability;
+pub(crate) mod scatter;
+pub(crate) mod hilbert;
+pub(crate) mod spiral;
+pub(crate) mod grid;
+pub(crate) mod scramble;
+pub(crate) mod chords;
+pub(crate) mod heatmap;
+pub(crate) mod mesh;
+
+use crate::sketch::activity;
+use crate::sketch::player::SortPlayer;
+use nannou::color::{hsv, Hsv, Srgb};
+use nannou::geom::{Vec2, Vec3};
+use nannou::Draw;
+use nannou_egui::egui::{self, ComboBox};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+/// A way of drawing playback. Each renderer keeps its own settings, and any state it needs
+/// between frames
+pub trait Renderer {
+    fn name(&self) -> &'static str;
+    /// Draws into the unit square `draw` is scaled to. `aspect` is the window's width over its height
+    fn draw(&self, player: &SortPlayer, draw: &Draw, aspect: f32);
+    /// Controls for the renderer's settings, shown in the settings window
+    fn settings(&mut self, _ui: &mut egui::Ui) {}
+    /// Called every frame before drawing, which is where renderers bring their meshes up to date
+    fn update(&mut self, _player: &SortPlayer, _aspect: f32) {}
+    /// Called when the window is resized, with its new size in points
+    fn on_resize(&mut self, _width: f32, _height: f32) {}
+    /// Called when playback is replaced, e.g. by a new algorithm, length or imported trace
+    fn on_restart(&mut self, _player: &SortPlayer) {}
+    /// Called with a file dropped onto the window, returns whether the renderer took it
+    #[cfg(not(target_family = "wasm"))]
+    fn on_dropped_file(&mut self, _path: &Path) -> bool {
+        false
+    }
+}
+
+/// Every renderer, in the order they are listed. The first ten are bound to the number keys 1 to
+/// 9 then 0
+pub struct Renderers {
+    all: Vec<Box<dyn Renderer>>,
+    current: usize,
+}
+
+impl Default for Renderers {
+    fn default() -> Self {
+        Self {
+            all: vec![
+                Box::new(classic::Classic::default()),
+                Box::new(disparity_dots::DisparityDots::default()),
+                Box::new(colour_circle::ColourCircle::default()),
+                Box::new(colour_towers::ColourTowers::default()),
+                Box::new(stability::Stability::default()),
+                Box::new(scatter::Scatter::default()),
+                Box::new(hilbert::Hilbert::default()),
+                Box::new(spiral::Spiral::default()),
+                Box::new(grid::Grid::default()),
+                Box::new(scramble::Scramble::default()),
+                Box::new(chords::Chords::default()),
+                Box::new(heatmap::Heatmap::default()),
+            ],
+            current: 0,
+        }
+    }
+}
+
+impl Renderers {
+    pub fn current(&self) -> &dyn Renderer {
+        self.all[self.current].as_ref()
+    }
+    pub fn current_mut(&mut self) -> &mut dyn Renderer {
+        self.all[self.current].as_mut()
+    }
+    /// Switches to the renderer at `index`, if there is one
+    pub fn select(&mut self, index: usize) {
+        if index < self.all.len() {
+            self.current = index;
+        }
+    }
+    /// Name of the renderer at `index` along with its number key, if it has one
+    pub fn label(&self, index: usize) -> String {
+        match index {
+            0..9 => format!("{} ({})", self.all[index].name(), index + 1),
+            9 => format!("{} (0)", self.all[index].name()),
+            _ => self.all[index].name().to_string(),
+        }
+    }
+    /// A combo box for picking the renderer, followed by its settings
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut selected = self.current;
+        ComboBox::from_label("Renderer")
+            .selected_text(self.label(self.current))
+            .show_ui(ui, |ui| {
+                for index in 0..self.all.len() {
+                    ui.selectable_value(&mut selected, index, self.label(index));
+                }
+            });
+        self.current = selected;
+        self.current_mut().settings(ui);
+        activity::legend(ui);
+    }
+    pub fn on_resize(&mut self, width: f32, height: f32) {
+        self.all.iter_mut().for_each(|r| r.on_resize(width, height));
+    }
+    pub fn on_restart(&mut self, player: &SortPlayer) {
+        self.all.iter_mut().for_each(|r| r.on_restart(player));
+    }
+    /// Offers a dropped file to each renderer and switches to the first that takes it
+    #[cfg(not(target_family = "wasm"))]
+    pub fn on_dropped_file(&mut self, path: &Path) -> bool {
+        let Some(index) = self.all.iter_mut().position(|r| r.on_dropped_file(path)) else {
+            return false;
+        };
+        self.current = index;
+        true
+    }
+}
+
+/// Saturation and brightness of elements with no recent activity, which highlights build on
+pub const RESTING: (f32, f32) = (0.8, 0.5);
+
+/// Axis-aligned rectangles collected into a single mesh, which is much cheaper to draw than a
+/// primitive per rectangle
+#[derive(Default)]
+pub struct Quads {
+    points: Vec<(Vec3, Srgb)>,
+}
+
+impl Quads {
+    pub fn push(&mut self, bottom_left: Vec2, size: Vec2, colour: Srgb) {
+        for corner in [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y] {
+            self.points.push(((bottom_left + corner * size).extend(0.0), colour));
+        }
+    }
+    /// Draws several sets of quads as one mesh
+    pub fn draw_all<'a>(all: impl IntoIterator<Item = &'a Quads, IntoIter: Clone>, draw: &Draw) {
+        let all = all.into_iter();
+        let quads: usize = all.clone().map(|q| q.points.len() / 4).sum();
+        if quads == 0 {
+            return;
+        }
+        let indices = (0..quads).flat_map(|q| [0, 1, 2, 0, 2, 3].map(|v| q * 4 + v));
+        draw.mesh().indexed_colored(all.flat_map(|q| q.points.iter().copied()), indices);
+    }
+}
+
+/// Colour schemes for renderers that colour elements by value
+#[derive(Debug, Default, PartialEq, Copy, Clone, EnumIter)]
+pub enum Palette {
+    #[default]
+    Rainbow,
+    Heat,
+    Greyscale,
+}
+
+impl Palette {
+    /// Colour of proportion `t` of the way through the palette, with saturation and brightness
+    /// usually `RESTING`, before `ActivityModel::tint` highlights it
+    pub fn colour(&self, t: f32, s: f32, v: f32) -> Hsv {
+        match self {
+            Palette::Rainbow => hsv(t, s, v),
+            Palette::Heat => hsv(t * 0.17, s, v * (0.4 + 0.6 * t)),
+            Palette::Greyscale => hsv(0.0, 0.0, v * (0.15 + 0.85 * t)),
+        }
+    }
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ComboBox::from_label("Palette")
+            .selected_text(format!("{self:?}"))
+            .show_ui(ui, |ui| {
+                for option in Palette::iter() {
+                    ui.selectable_value(self, option, format!("{option:?}"));
+                }
+            });
+    }
+}
@@ -0,0 +1,80 @@
+use nannou::color::{hsv, Srgb};
+use nannou::Draw;
+use nannou::geom::Vec2;
+use nannou_egui::egui::{self, Slider};
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{ellipse, ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
+
+/// Sides of the polygon each point is drawn as
+const POINT_SIDES: usize = 12;
+
+/// A point per element at (index, value). Sorted runs show up as diagonal streaks, and the whole
+/// list settles onto the diagonal once sorted
+pub struct Scatter {
+    palette: Palette,
+    /// Join consecutive points with a line
+    connect: bool,
+    /// Draw the line every point ends up on
+    diagonal: bool,
+    point_scale: f32,
+    mesh: ElementMesh<(Palette, f32, f32)>,
+}
+
+impl Default for Scatter {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            connect: false,
+            diagonal: true,
+            point_scale: 1.0,
+            mesh: ElementMesh::new(Shape::polygon(POINT_SIDES)),
+        }
+    }
+}
+
+impl Renderer for Scatter {
+    fn name(&self) -> &'static str {
+        "Scatter"
+    }
+
+    fn draw(&self, player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        let length = player.length as f32;
+        // Inset so points at the edges aren't cut in half
+        let draw = draw.xy(Vec2::splat(0.05)).scale(0.9);
+
+        if self.diagonal {
+            draw.line().start(Vec2::ZERO).end(Vec2::ONE).weight(0.002).color(hsv(0.0, 0.0, 0.3));
+        }
+        if self.connect {
+            let points = player.playback_vec.iter().enumerate().map(|(i, &x)| {
+                let colour: Srgb = player.activity.tint(i, self.palette.colour(x as f32 / length, RESTING.0, RESTING.1));
+                (position(i, x, length), colour)
+            });
+            draw.polyline().weight(0.002).points_colored(points);
+        }
+        self.mesh.draw(&draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ui.checkbox(&mut self.connect, "Connect points");
+        ui.checkbox(&mut self.diagonal, "Diagonal reference");
+        ui.add(Slider::new(&mut self.point_scale, 0.25..=8.0).logarithmic(true).text("Point size"));
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let size = (2.0 / length).max(0.004) * self.point_scale;
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, self.point_scale, aspect), |i, points| {
+            let x = player.playback_vec[i];
+            ellipse(points, position(i, x, length), Vec2::new(size / aspect, size) / 2.0);
+            palette.colour(x as f32 / length, RESTING.0, RESTING.1)
+        });
+    }
+}
+
+fn position(i: usize, x: usize, length: f32) -> Vec2 {
+    Vec2::new((i as f32 + 0.5) / length, (x as f32 + 0.5) / length)
+}
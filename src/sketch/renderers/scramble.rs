@@ -0,0 +1,205 @@
+use nannou::Draw;
+use nannou::color::{Hsv, Srgb};
+use nannou::geom::Vec2;
+use nannou::image::{self, imageops::FilterType, RgbImage};
+use nannou_egui::egui::{self, ComboBox};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::Renderer;
+use crate::sketch::renderers::mesh::{self, ElementMesh, Shape};
+
+/// Shown until another image is loaded
+const DEFAULT_IMAGE: &[u8] = include_bytes!("../../../assets/sunset.png");
+/// Most cells the image is resampled to, which bounds the size of the mesh drawn each frame
+const MAX_CELLS: usize = 1 << 16;
+
+/// A picture cut into a piece per element, drawn in the order of `playback_vec`. The value of an
+/// element is the position of its piece, so the picture reassembles as the list is sorted
+pub struct Scramble {
+    image: RgbImage,
+    slices: Slices,
+    /// Pieces of the image resampled for the current length and slicing, row-major
+    pieces: Vec<Hsv>,
+    /// Columns and rows of `pieces`, along with the length and slicing they were cut for
+    cut: Option<Cut>,
+    /// A quad per piece of each element, for the cut and window aspect ratio it was built for
+    mesh: ElementMesh<(Cut, f32)>,
+    #[cfg(not(target_family = "wasm"))]
+    path: String,
+    /// Result of the last image load, shown in the settings
+    status: Option<String>,
+}
+
+impl Default for Scramble {
+    fn default() -> Self {
+        let image = image::load_from_memory(DEFAULT_IMAGE).expect("the bundled image is a valid PNG").to_rgb8();
+        Self {
+            image,
+            slices: Slices::Columns,
+            pieces: vec![],
+            cut: None,
+            mesh: ElementMesh::new(Shape::quads(1)),
+            #[cfg(not(target_family = "wasm"))]
+            path: String::new(),
+            status: None,
+        }
+    }
+}
+
+/// How the picture is divided between elements
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+enum Slices {
+    /// A full height column per element, left to right
+    Columns,
+    /// A pixel per element, row-major from the top left
+    Pixels,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+struct Cut {
+    length: usize,
+    slices: Slices,
+    columns: usize,
+    rows: usize,
+}
+
+impl Scramble {
+    /// Resamples the image into pieces for `length` elements
+    fn cut(&mut self, length: usize) {
+        let (width, height) = (self.image.width() as f32, self.image.height() as f32);
+        let (columns, rows) = match self.slices {
+            // Roughly square pixels, unless that would make too many cells
+            Slices::Columns => (length, ((length as f32 * height / width).round() as usize).clamp(1, (MAX_CELLS / length.max(1)).max(1))),
+            Slices::Pixels => {
+                let columns = ((length as f32 * width / height).sqrt().ceil() as usize).clamp(1, length.max(1));
+                (columns, length.div_ceil(columns).max(1))
+            }
+        };
+        let resized = image::imageops::resize(&self.image, columns as u32, rows as u32, FilterType::Triangle);
+        self.pieces = resized.pixels().map(|p| Hsv::from(Srgb::new(p[0], p[1], p[2]).into_format::<f32>())).collect();
+        self.cut = Some(Cut { length, slices: self.slices, columns, rows });
+        let pieces_per_element = match self.slices {
+            Slices::Columns => rows,
+            Slices::Pixels => 1,
+        };
+        self.mesh = ElementMesh::new(Shape::quads(pieces_per_element));
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn load(&mut self) {
+        self.status = Some(match image::open(&self.path) {
+            Ok(image) => {
+                self.image = image.to_rgb8();
+                self.cut = None;
+                format!("Loaded {}", self.path)
+            }
+            Err(e) => format!("Failed to load {}: {}", self.path, e),
+        });
+    }
+}
+
+impl Renderer for Scramble {
+    fn name(&self) -> &'static str {
+        "Image scramble"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ComboBox::from_label("Slices")
+            .selected_text(format!("{:?}", self.slices))
+            .show_ui(ui, |ui| {
+                for option in Slices::iter() {
+                    ui.selectable_value(&mut self.slices, option, format!("{option:?}"));
+                }
+            });
+        #[cfg(not(target_family = "wasm"))]
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.path).hint_text("Image path, or drop one on the window"));
+            if ui.button("Load image").clicked() {
+                self.load();
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn on_dropped_file(&mut self, path: &Path) -> bool {
+        if image::ImageFormat::from_path(path).is_err() {
+            return false;
+        }
+        self.path = path.display().to_string();
+        self.load();
+        true
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let current = self.cut.is_some_and(|cut| cut.length == player.length && cut.slices == self.slices);
+        if !current {
+            self.cut(player.length);
+        }
+        let Some(cut) = self.cut else {
+            return;
+        };
+        // Cells the same shape as the image's pixels, in the middle of the window
+        let image_aspect = self.image.width() as f32 / self.image.height() as f32;
+        let fit = (aspect / image_aspect).min(1.0);
+        let area = Vec2::new(fit * image_aspect / aspect, fit);
+        let offset = (Vec2::ONE - area) / 2.0;
+        let cell = area / Vec2::new(cut.columns as f32, cut.rows as f32);
+        let quad = |points: &mut [Vec2], column: usize, row: usize| {
+            let bottom_left = offset + Vec2::new(column as f32, (cut.rows - 1 - row) as f32) * cell;
+            mesh::rect(points, bottom_left + cell / 2.0, cell);
+        };
+        let missing = Hsv::new(0.0, 0.0, 0.0);
+
+        let pieces = &self.pieces;
+        self.mesh.update_shaded(player, (cut, aspect), |i, points, colours| {
+            let x = player.playback_vec[i];
+            match cut.slices {
+                Slices::Columns => {
+                    for row in 0..cut.rows {
+                        quad(&mut points[row * 4..(row + 1) * 4], i, row);
+                        colours[row * 4..(row + 1) * 4].fill(pieces.get(row * cut.columns + x).copied().unwrap_or(missing));
+                    }
+                }
+                Slices::Pixels => {
+                    quad(points, i % cut.columns, i / cut.columns);
+                    colours.fill(pieces.get(x).copied().unwrap_or(missing));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_value_has_a_piece() {
+        let mut scramble = Scramble::default();
+        for slices in Slices::iter() {
+            scramble.slices = slices;
+            for length in [1, 2, 8, 256, 1 << 16] {
+                scramble.cut(length);
+                let cut = scramble.cut.unwrap();
+                assert_eq!(scramble.pieces.len(), cut.columns * cut.rows);
+                match slices {
+                    Slices::Columns => {
+                        assert_eq!(cut.columns, length);
+                        assert!(scramble.pieces.len() <= MAX_CELLS.max(length));
+                    }
+                    Slices::Pixels => assert!(scramble.pieces.len() >= length),
+                }
+            }
+        }
+    }
+}
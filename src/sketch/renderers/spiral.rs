@@ -0,0 +1,57 @@
+use std::f32::consts::{PI, TAU};
+use nannou::Draw;
+use nannou::geom::Vec2;
+use nannou_egui::egui::{self, Slider};
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{ElementMesh, Shape};
+use crate::sketch::renderers::{Palette, Renderer, RESTING};
+
+/// The colour circle wound into an Archimedean spiral, so each element gets a patch of roughly
+/// equal area however long the list is
+pub struct Spiral {
+    palette: Palette,
+    /// Multiplies the number of turns, which by default makes each patch about square
+    turns_scale: f32,
+    mesh: ElementMesh<(Palette, f32, f32)>,
+}
+
+impl Default for Spiral {
+    fn default() -> Self {
+        Self { palette: Palette::default(), turns_scale: 1.0, mesh: ElementMesh::new(Shape::quads(1)) }
+    }
+}
+
+impl Renderer for Spiral {
+    fn name(&self) -> &'static str {
+        "Spiral"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(&draw.scale(0.5).xy(Vec2::splat(1.0)));
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        self.palette.ui(ui);
+        ui.add(Slider::new(&mut self.turns_scale, 0.1..=4.0).logarithmic(true).text("Turns"));
+    }
+
+    fn update(&mut self, player: &SortPlayer, aspect: f32) {
+        let length = player.length as f32;
+        let turns = ((length / PI).sqrt() * self.turns_scale).max(1.0);
+        let half_band = 0.45 / turns;
+        // Elements are spaced evenly along the curve, which for r = θ / (turns τ) means θ ∝ √t
+        let point = |i: f32, offset: f32| {
+            let r = (i / length).sqrt();
+            let theta = r * turns * TAU;
+            let r = (r * (1.0 - half_band) + offset).max(0.0) * 0.95;
+            Vec2::new(theta.cos() / aspect, theta.sin()) * r
+        };
+
+        let palette = self.palette;
+        self.mesh.update(player, (self.palette, self.turns_scale, aspect), |i, points| {
+            let (start, end) = (i as f32, i as f32 + 1.0);
+            points.copy_from_slice(&[point(start, -half_band), point(end, -half_band), point(end, half_band), point(start, half_band)]);
+            palette.colour(player.playback_vec[i] as f32 / length, RESTING.0, RESTING.1)
+        });
+    }
+}
@@ -0,0 +1,51 @@
+use nannou::color::hsv;
+use nannou::Draw;
+use nannou::geom::Vec2;
+use nannou_egui::egui;
+use crate::sketch::list::UNKNOWN_ORIGIN;
+use crate::sketch::player::SortPlayer;
+use crate::sketch::renderers::mesh::{ElementMesh, Shape};
+use crate::sketch::renderers::{Renderer, RESTING};
+
+/// Bars coloured by where each element was when sorting started, rather than by value. Equal
+/// values sorted stably end up as a smooth run of hues, unstably as shuffled ones
+pub struct Stability {
+    /// Gap between bars, which helps tell neighbouring hues apart at small lengths
+    outline: bool,
+    mesh: ElementMesh<bool>,
+}
+
+impl Default for Stability {
+    fn default() -> Self {
+        Self { outline: false, mesh: ElementMesh::new(Shape::quads(1)) }
+    }
+}
+
+impl Renderer for Stability {
+    fn name(&self) -> &'static str {
+        "Stability"
+    }
+
+    fn draw(&self, _player: &SortPlayer, draw: &Draw, _aspect: f32) {
+        self.mesh.draw(draw);
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.outline, "Outline bars");
+    }
+
+    fn update(&mut self, player: &SortPlayer, _aspect: f32) {
+        let length = player.length as f32;
+        let gap = if self.outline { 0.1 / length } else { 0.0 };
+        self.mesh.update(player, self.outline, |i, points| {
+            let height = (player.playback_vec[i] + 1) as f32 / length;
+            let (left, right) = (i as f32 / length + gap / 2.0, (i + 1) as f32 / length - gap / 2.0);
+            points.copy_from_slice(&[Vec2::new(left, 0.0), Vec2::new(right, 0.0), Vec2::new(right, height), Vec2::new(left, height)]);
+            let (s, v) = RESTING;
+            // Elements written without their origin can't be told apart, so they are grey
+            let origin = player.playback_origins.get(i).copied().unwrap_or(UNKNOWN_ORIGIN);
+            let (h, s) = if origin == UNKNOWN_ORIGIN { (0.0, 0.0) } else { (origin as f32 / length, s) };
+            hsv(h, s, v)
+        });
+    }
+}
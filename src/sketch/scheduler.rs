@@ -0,0 +1,63 @@
+use std::collections::VecDeque;
+
+/// Longest frame gap that is turned into ops, so a stalled window doesn't replay a burst on return
+const MAX_FRAME_GAP: f64 = 0.25;
+
+/// Converts wall-clock time into a whole number of ops per frame, carrying the fractional part
+/// between frames so the long run throughput matches the requested rate exactly
+#[derive(Debug, Default)]
+pub struct PlaybackScheduler {
+    owed: f64,
+    history: VecDeque<(f64, usize)>,
+}
+
+impl PlaybackScheduler {
+    /// Number of ops due after `dt` seconds at `rate` ops/second
+    pub fn due(&mut self, dt: f64, rate: f64) -> usize {
+        self.owed += rate.max(0.0) * dt.clamp(0.0, MAX_FRAME_GAP);
+        let ops = self.owed.floor();
+        self.owed -= ops;
+        ops as usize
+    }
+
+    /// Records that `ops` were actually played at time `now`, for `measured_rate`
+    pub fn record(&mut self, now: f64, ops: usize, rate: f64) {
+        self.history.push_back((now, ops));
+        // Slow rates need a longer window to see more than one op
+        let window = (4.0 / rate.max(f64::EPSILON)).clamp(1.0, 30.0);
+        while self.history.front().is_some_and(|&(t, _)| now - t > window) {
+            self.history.pop_front();
+        }
+    }
+
+    /// Ops per second actually played over the recent window
+    pub fn measured_rate(&self) -> f64 {
+        let (Some(&(first, _)), Some(&(last, _))) = (self.history.front(), self.history.back()) else {
+            return 0.0;
+        };
+        let span = last - first;
+        if span <= 0.0 {
+            return 0.0;
+        }
+        // The first entry's ops were played before the window started
+        let ops: usize = self.history.iter().skip(1).map(|&(_, n)| n).sum();
+        ops as f64 / span
+    }
+
+    /// Frames per second over the same window, as each frame records once
+    pub fn frame_rate(&self) -> f64 {
+        let (Some(&(first, _)), Some(&(last, _))) = (self.history.front(), self.history.back()) else {
+            return 0.0;
+        };
+        let span = last - first;
+        if span <= 0.0 {
+            return 0.0;
+        }
+        (self.history.len() - 1) as f64 / span
+    }
+
+    pub fn reset(&mut self) {
+        self.owed = 0.0;
+        self.history.clear();
+    }
+}
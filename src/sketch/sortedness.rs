@@ -0,0 +1,143 @@
+use crate::sketch::renderers::disparity_dots::radius;
+use std::fmt::Formatter;
+use strum_macros::EnumIter;
+
+/// Ways of measuring how far a list is from sorted
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter)]
+pub enum Measure {
+    /// Pairs in the wrong order
+    Inversions,
+    /// Longest non-decreasing subsequence
+    IncreasingSubsequence,
+    /// Maximal non-decreasing runs
+    Runs,
+    /// Total distance of elements from their sorted positions, the short way round
+    Displacement,
+    /// Inversions as a proportion of the most there could be
+    KendallTau,
+}
+
+impl Measure {
+    pub fn of(&self, values: &[usize]) -> f64 {
+        match self {
+            Measure::Inversions => inversions(values) as f64,
+            Measure::IncreasingSubsequence => longest_increasing(values) as f64,
+            Measure::Runs => runs(values) as f64,
+            Measure::Displacement => displacement(values),
+            Measure::KendallTau => kendall_tau_distance(values),
+        }
+    }
+}
+
+impl std::fmt::Display for Measure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Measure::Inversions => "Inversions",
+            Measure::IncreasingSubsequence => "Longest increasing subsequence",
+            Measure::Runs => "Runs",
+            Measure::Displacement => "Displacement",
+            Measure::KendallTau => "Kendall tau distance",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Pairs `i < j` with `values[i] > values[j]`, counted while merge sorting a copy
+pub fn inversions(values: &[usize]) -> u64 {
+    fn count(values: &mut [usize], buffer: &mut Vec<usize>) -> u64 {
+        if values.len() < 2 {
+            return 0;
+        }
+        let mid = values.len() / 2;
+        let mut total = count(&mut values[..mid], buffer) + count(&mut values[mid..], buffer);
+        buffer.clear();
+        let (mut i, mut j) = (0, mid);
+        while i < mid && j < values.len() {
+            if values[j] < values[i] {
+                // Everything left in the first half is greater
+                total += (mid - i) as u64;
+                buffer.push(values[j]);
+                j += 1;
+            } else {
+                buffer.push(values[i]);
+                i += 1;
+            }
+        }
+        buffer.extend_from_slice(&values[i..mid]);
+        buffer.extend_from_slice(&values[j..]);
+        values.copy_from_slice(buffer);
+        total
+    }
+    count(&mut values.to_vec(), &mut Vec::with_capacity(values.len()))
+}
+
+/// Length of the longest non-decreasing subsequence
+pub fn longest_increasing(values: &[usize]) -> usize {
+    // Smallest tail of any subsequence of each length
+    let mut tails: Vec<usize> = vec![];
+    for &x in values {
+        let at = tails.partition_point(|&t| t <= x);
+        match tails.get_mut(at) {
+            Some(tail) => *tail = x,
+            None => tails.push(x),
+        }
+    }
+    tails.len()
+}
+
+/// Maximal non-decreasing runs, which is 1 for a sorted list
+pub fn runs(values: &[usize]) -> usize {
+    match values.len() {
+        0 => 0,
+        _ => 1 + values.windows(2).filter(|w| w[1] < w[0]).count(),
+    }
+}
+
+/// Sum of how far each element is from the index matching its value, measured around the circle
+/// the way the disparity renderers do
+pub fn displacement(values: &[usize]) -> f64 {
+    let length = values.len() as f32;
+    values.iter().enumerate().map(|(i, &x)| ((1.0 - radius(i, x, length)) / 2.0 * length) as f64).sum()
+}
+
+/// Normalised Kendall tau distance to the sorted list: 0 when sorted, 1 when reversed
+pub fn kendall_tau_distance(values: &[usize]) -> f64 {
+    let n = values.len() as f64;
+    match values.len() {
+        0 | 1 => 0.0,
+        _ => inversions(values) as f64 / (n * (n - 1.0) / 2.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_inversions(values: &[usize]) -> u64 {
+        let mut total = 0;
+        for i in 0..values.len() {
+            for j in i + 1..values.len() {
+                total += (values[i] > values[j]) as u64;
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn measures_of_known_lists() {
+        let sorted: Vec<usize> = (0..8).collect();
+        let reversed: Vec<usize> = (0..8).rev().collect();
+        assert_eq!((inversions(&sorted), longest_increasing(&sorted), runs(&sorted)), (0, 8, 1));
+        assert_eq!((inversions(&reversed), longest_increasing(&reversed), runs(&reversed)), (28, 1, 8));
+        assert_eq!(displacement(&sorted), 0.0);
+        assert_eq!(kendall_tau_distance(&sorted), 0.0);
+        assert_eq!(kendall_tau_distance(&reversed), 1.0);
+        assert_eq!((inversions(&[]), longest_increasing(&[]), runs(&[])), (0, 0, 0));
+
+        let values = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+        assert_eq!(inversions(&values), brute_inversions(&values));
+        // 1, 1, 2, 3, 5
+        assert_eq!(longest_increasing(&values), 5);
+        assert_eq!(runs(&values), 6);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::sketch::player::SortPlayer;
+use crate::sketch::sortedness::Measure;
+use egui_plot::{Line, Plot, PlotPoints};
+use nannou_egui::egui::{self, Window};
+use strum::IntoEnumIterator;
+
+/// Samples kept before every other one is dropped
+const MAX_SAMPLES: usize = 4096;
+
+/// Measures of sortedness of the playback list, sampled as it plays and graphed against op index
+#[derive(Default)]
+pub struct SortednessView {
+    pub open: bool,
+    /// Op index and the value of each `Measure`, in order of op index
+    samples: Vec<(usize, Vec<f64>)>,
+}
+
+impl SortednessView {
+    /// Forgets the samples, e.g. when playback is replaced
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Samples the list at the playback point. Only done while the window is open, as it costs
+    /// O(n log n) each frame
+    fn sample(&mut self, player: &SortPlayer) {
+        let point = player.current_play_back_point;
+        // Seeking back replays from the start, so later samples no longer apply
+        while self.samples.last().is_some_and(|&(op, _)| op > point) {
+            self.samples.pop();
+        }
+        if self.samples.last().is_some_and(|&(op, _)| op == point) {
+            return;
+        }
+        let values = Measure::iter().map(|m| m.of(&player.playback_vec)).collect();
+        self.samples.push((point, values));
+        if self.samples.len() > MAX_SAMPLES {
+            let mut keep = false;
+            self.samples.retain(|_| {
+                keep = !keep;
+                keep
+            });
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, player: &SortPlayer) {
+        if !self.open {
+            return;
+        }
+        self.sample(player);
+        let mut open = self.open;
+        Window::new("Sortedness").open(&mut open).show(ctx, |ui| {
+            let latest = self.samples.last().map(|(_, values)| values.as_slice()).unwrap_or_default();
+            for (m, measure) in Measure::iter().enumerate() {
+                let value = latest.get(m).copied().unwrap_or_default();
+                ui.label(format!("{measure}: {}", format_value(measure, value)));
+                let points: PlotPoints = self.samples.iter().map(|(op, values)| [*op as f64, values[m]]).collect();
+                Plot::new(("sortedness_plot", m))
+                    .height(70.0)
+                    .link_axis("sortedness", true, false)
+                    .allow_scroll(false)
+                    .show(ui, |plot| plot.line(Line::new(points).name(measure.to_string())));
+            }
+            ui.label("Against ops played");
+        });
+        self.open = open;
+    }
+}
+
+fn format_value(measure: Measure, value: f64) -> String {
+    match measure {
+        Measure::KendallTau => format!("{value:.3}"),
+        _ => format!("{value:.0}"),
+    }
+}
@@ -0,0 +1,286 @@
+use crate::sketch::list::Operation;
+use crate::sketch::methods::SortMethod;
+use crate::sketch::trace_io::Trace;
+use crate::sketch::{shuffle_step_by_step, zing, List, ListPart, Stopped};
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// How many events the sort may get ahead of playback, which bounds how long each step of it runs.
+/// The channel allocates room for all of them up front
+const CHANNEL_CAPACITY: usize = 1 << 12;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum TraceEvent {
+    Op(Operation),
+    /// The algorithm started a new phase before the next op
+    Phase,
+    /// Origin of the element written by the next set, only sent when it isn't the one playback
+    /// predicts: the first position of the element's level when origins were taken
+    Origin(usize),
+    /// Each element's position became its origin before the next op
+    Retag,
+    /// The element at this index was compared before the next op
+    Compare(usize),
+}
+
+/// Where a streaming `List` sends its events
+pub(crate) type TraceSink = SyncSender<TraceEvent>;
+
+/// Sends `event` to playback, waiting while the channel is full. Stops the sort if the player was
+/// dropped
+pub(crate) async fn send(sink: &TraceSink, event: TraceEvent) -> Result<(), Stopped> {
+    let mut event = Some(event);
+    poll_fn(|_| match sink.try_send(event.take().expect("polled after sending")) {
+        Ok(()) => Poll::Ready(Ok(())),
+        Err(TrySendError::Full(unsent)) => {
+            event = Some(unsent);
+            Poll::Pending
+        }
+        Err(TrySendError::Disconnected(_)) => Poll::Ready(Err(Stopped::Cancelled)),
+    })
+    .await
+}
+
+/// Runs a sort to the end on this thread. Only a list streaming into a full channel ever waits,
+/// so this is for lists without a sink
+pub(crate) fn block_on<F: Future>(sort: F) -> F::Output {
+    let mut sort = pin!(sort);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = sort.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+type Sort = Pin<Box<dyn Future<Output = anyhow::Result<()>>>>;
+
+/// Where a trace comes from. It can be produced again from the start, giving the same events each
+/// time, so nothing already played has to be kept
+trait TraceSource: Send + Sync {
+    /// Sends the events to `sink`, pausing whenever it is full
+    fn stream(self: Arc<Self>, sink: TraceSink) -> Sort;
+    /// The whole trace, run to the end on this thread. A sort that stopped early gives the ops up
+    /// to where it stopped
+    #[cfg(not(target_family = "wasm"))]
+    fn record(&self) -> Trace;
+    /// Bytes of heap held by the source, and by the run of it being streamed if `running`
+    fn memory_bytes(&self, running: bool) -> usize;
+}
+
+/// A sort of `list`, which is cloned for each run so every run starts from the same input and
+/// makes the same random choices
+struct Generated<T> {
+    list: List<T>,
+    method: SortMethod,
+    shuffle: bool,
+}
+
+impl<T: Clone + Send + Sync + 'static> TraceSource for Generated<T> {
+    fn stream(self: Arc<Self>, sink: TraceSink) -> Sort {
+        Box::pin(async move {
+            let mut list = self.list.clone();
+            list.sink = Some(sink);
+            generate(&mut list, self.method, self.shuffle).await
+        })
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn record(&self) -> Trace {
+        let mut list = self.list.clone();
+        // The error is shown by playback, the ops before it are still worth having
+        let _ = block_on(generate(&mut list, self.method, self.shuffle));
+        Trace {
+            length: list.length,
+            starting_vec: self.list.levels().to_vec(),
+            operations: list.record_of_operations,
+            phase_boundaries: list.phase_boundaries,
+        }
+    }
+
+    fn memory_bytes(&self, running: bool) -> usize {
+        // The run sorts its own copy of the list
+        self.list.memory_bytes() * if running { 2 } else { 1 }
+    }
+}
+
+/// A trace recorded earlier, e.g. an imported one
+impl TraceSource for Trace {
+    fn stream(self: Arc<Self>, sink: TraceSink) -> Sort {
+        Box::pin(async move {
+            let mut phases = self.phase_boundaries.iter().peekable();
+            for (n, op) in self.operations.iter().enumerate() {
+                while phases.next_if(|&&p| p == n).is_some() {
+                    send(&sink, TraceEvent::Phase).await?;
+                }
+                send(&sink, TraceEvent::Op(op)).await?;
+            }
+            for _ in phases {
+                send(&sink, TraceEvent::Phase).await?;
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn record(&self) -> Trace {
+        self.clone()
+    }
+
+    fn memory_bytes(&self, _running: bool) -> usize {
+        let indices = self.starting_vec.capacity() + self.phase_boundaries.capacity();
+        self.operations.memory_bytes() + indices * size_of::<usize>()
+    }
+}
+
+/// Operations of a sort, produced as playback asks for them. The sort runs on the calling thread,
+/// a step at a time: it is resumed whenever playback has used up its events, and pauses again once
+/// the channel is full, so no step takes longer than `CHANNEL_CAPACITY` events
+pub(crate) struct TraceStream {
+    source: Arc<dyn TraceSource>,
+    /// `None` once the sort has returned
+    sort: Option<Sort>,
+    receiver: Receiver<TraceEvent>,
+    pub(crate) finished: bool,
+    /// Why the sort stopped early, if it did
+    pub(crate) error: Option<String>,
+}
+
+impl TraceStream {
+    pub(crate) fn new<T: Clone + Send + Sync + 'static>(list: List<T>, method: SortMethod, shuffle: bool) -> Self {
+        Self::of(Arc::new(Generated { list, method, shuffle }))
+    }
+
+    /// Plays back a recorded trace
+    pub(crate) fn recorded(trace: Trace) -> Self {
+        Self::of(Arc::new(trace))
+    }
+
+    fn of(source: Arc<dyn TraceSource>) -> Self {
+        let (sink, receiver) = sync_channel(CHANNEL_CAPACITY);
+        let sort = source.clone().stream(sink);
+        Self { source, sort: Some(sort), receiver, finished: false, error: None }
+    }
+
+    /// A new stream of the same trace, from its first event
+    pub(crate) fn fresh(&self) -> Self {
+        Self::of(self.source.clone())
+    }
+
+    /// Starts the trace again from its first event
+    pub(crate) fn restart(&mut self) {
+        *self = self.fresh();
+    }
+
+    /// Produces the whole trace again from the start when called, which can be on another thread
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn recorder(&self) -> impl FnOnce() -> Trace + Send + 'static {
+        let source = self.source.clone();
+        move || source.record()
+    }
+
+    /// Bytes of heap held to produce the trace, including the channel's buffer
+    pub(crate) fn memory_bytes(&self) -> usize {
+        // Each slot of the channel holds an event and a stamp
+        CHANNEL_CAPACITY * size_of::<(usize, TraceEvent)>() + self.source.memory_bytes(self.sort.is_some())
+    }
+
+    /// Next event, running the sort until it produces one. `None` once the sort has finished
+    pub(crate) fn next(&mut self) -> Option<TraceEvent> {
+        while !self.finished {
+            if let Ok(event) = self.receiver.try_recv() {
+                return Some(event);
+            }
+            match &mut self.sort {
+                Some(sort) => {
+                    if let Poll::Ready(result) = sort.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+                        self.error = result.err().map(|e| format!("{e:#}"));
+                        // Dropping the sort drops its sink, after which only the events already sent are left
+                        self.sort = None;
+                    }
+                }
+                None => self.finished = true,
+            }
+        }
+        None
+    }
+}
+
+/// Shuffles (if asked), sorts, then sweeps the list to show it is sorted. Origins are taken after
+/// the shuffle, so they are the positions the sort started from
+pub(crate) async fn generate<T: Clone>(list: &mut List<T>, method: SortMethod, shuffle: bool) -> anyhow::Result<()> {
+    if shuffle {
+        shuffle_step_by_step(list).await?;
+    }
+    list.retag().await?;
+    if !list.is_sorted() {
+        method.run(list).await?;
+    }
+    list.mark_phase().await?;
+    zing(list).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn a_stepped_sort_streams_the_trace_it_records() {
+        // Long enough for the slower sorts to fill the channel and be resumed several times
+        let list = List::new((0..400).map(|i| i * 7 % 400).collect(), 400);
+        for method in SortMethod::iter().filter(|&m| m != SortMethod::Bogo) {
+            let mut stream = TraceStream::new(list.clone(), method, false);
+            let (mut ops, mut phases) = (vec![], vec![]);
+            while let Some(event) = stream.next() {
+                match event {
+                    TraceEvent::Op(op) => ops.push(op),
+                    TraceEvent::Phase if phases.last() != Some(&ops.len()) => phases.push(ops.len()),
+                    _ => {}
+                }
+            }
+            assert_eq!(stream.error, None, "{method}");
+
+            let mut recorded = list.clone();
+            block_on(generate(&mut recorded, method, false)).unwrap();
+            assert_eq!(ops, recorded.record_of_operations.iter().collect::<Vec<_>>(), "{method}");
+            assert_eq!(phases, recorded.phase_boundaries, "{method}");
+        }
+    }
+
+    #[test]
+    fn only_origins_playback_cannot_predict_are_sent() {
+        let count = |input: Vec<usize>| {
+            let length = input.len();
+            let mut stream = TraceStream::new(List::new(input, length), SortMethod::Merge, true);
+            let events: Vec<_> = std::iter::from_fn(|| stream.next()).collect();
+            let sets = events.iter().filter(|e| matches!(e, TraceEvent::Op(Operation::Set(..)))).count();
+            let origins = events.iter().filter(|e| matches!(e, TraceEvent::Origin(_))).count();
+            (sets, origins)
+        };
+        // Every level is unique, so its first position is where the element started
+        let (sets, origins) = count((0..64).collect());
+        assert!(sets > 0);
+        assert_eq!(origins, 0);
+        // Only the first element of each run of equal levels can be predicted
+        let (sets, origins) = count((0..64).map(|i| i / 8 * 8).collect());
+        assert!(origins > 0 && origins < sets, "{origins} origins for {sets} sets");
+    }
+
+    #[test]
+    fn a_restarted_stream_repeats_its_random_choices() {
+        let ops = |stream: &mut TraceStream| {
+            let events = std::iter::from_fn(|| stream.next());
+            events.filter_map(|e| if let TraceEvent::Op(op) = e { Some(op) } else { None }).collect::<Vec<_>>()
+        };
+        let mut stream = TraceStream::new(List::new((0..5).collect(), 5), SortMethod::Bogo, true);
+        let first = ops(&mut stream);
+        stream.restart();
+        assert_eq!(ops(&mut stream), first);
+        assert_eq!(stream.recorder()().operations.iter().collect::<Vec<_>>(), first);
+    }
+}
@@ -0,0 +1,534 @@
+use crate::sketch::list::Operation;
+use crate::sketch::op_log::OpLog;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::fmt::{Formatter, Write};
+use strum_macros::{EnumIter, EnumString};
+
+/// Identifies the binary trace format, followed by `BINARY_VERSION`
+pub const BINARY_MAGIC: &[u8; 8] = b"SORTTRC\0";
+pub const BINARY_VERSION: u16 = 1;
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TraceFormat {
+    Json,
+    Csv,
+    Binary,
+}
+
+impl TraceFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TraceFormat::Json => "json",
+            TraceFormat::Csv => "csv",
+            TraceFormat::Binary => "strace",
+        }
+    }
+    #[cfg(target_family = "wasm")]
+    pub fn mime(&self) -> &'static str {
+        match self {
+            TraceFormat::Json => "application/json",
+            TraceFormat::Csv => "text/csv",
+            TraceFormat::Binary => "application/octet-stream",
+        }
+    }
+}
+
+impl std::fmt::Display for TraceFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} (.{})", self, self.extension())
+    }
+}
+
+/// Everything needed to replay a sort: the starting vector and the ops applied to it
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub length: usize,
+    pub starting_vec: Vec<usize>,
+    pub(crate) operations: OpLog,
+    pub phase_boundaries: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTrace {
+    version: u32,
+    length: usize,
+    starting_vec: Vec<usize>,
+    #[serde(default)]
+    phase_boundaries: Vec<usize>,
+    operations: Vec<JsonOp>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JsonOp {
+    Get { i: usize },
+    Set { i: usize, value: usize },
+    Swap { i: usize, j: usize },
+}
+
+/// Where the parts of a JSON trace are in its text, found again to point errors at them
+#[derive(Deserialize)]
+struct JsonLocations<'a> {
+    #[serde(borrow)]
+    starting_vec: &'a RawValue,
+    #[serde(borrow, default)]
+    phase_boundaries: Vec<&'a RawValue>,
+    #[serde(borrow)]
+    operations: Vec<&'a RawValue>,
+}
+
+impl<'a> JsonLocations<'a> {
+    fn new(text: &'a str) -> Self {
+        serde_json::from_str(text).expect("the text already parsed as a trace")
+    }
+    fn starting_values(&self) -> Vec<&'a RawValue> {
+        serde_json::from_str(self.starting_vec.get()).expect("the text already parsed as a trace")
+    }
+}
+
+/// Line and column of `raw` in `text`, which it is a slice of, counted the way serde_json does
+fn line_column(text: &str, raw: &RawValue) -> String {
+    let before = &text[..raw.get().as_ptr() as usize - text.as_ptr() as usize];
+    let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+    format!("line {}, column {}", before.matches('\n').count() + 1, before.len() - line_start + 1)
+}
+
+impl From<Operation> for JsonOp {
+    fn from(op: Operation) -> Self {
+        match op {
+            Operation::Get(i) => JsonOp::Get { i },
+            Operation::Set(i, value) => JsonOp::Set { i, value },
+            Operation::Swap(i, j) => JsonOp::Swap { i, j },
+        }
+    }
+}
+
+impl Trace {
+    pub fn encode(&self, format: TraceFormat) -> Vec<u8> {
+        match format {
+            TraceFormat::Json => self.to_json().into_bytes(),
+            TraceFormat::Csv => self.to_csv().into_bytes(),
+            TraceFormat::Binary => self.to_binary(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = JsonTrace {
+            version: JSON_VERSION,
+            length: self.length,
+            starting_vec: self.starting_vec.clone(),
+            phase_boundaries: self.phase_boundaries.clone(),
+            operations: self.operations.iter().map(JsonOp::from).collect(),
+        };
+        serde_json::to_string(&json).expect("trace serialises to json")
+    }
+
+    /// One `start` row per starting element, then one row per op with `phase` rows marking where
+    /// each phase begins
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("op,i,j/value\n");
+        for (i, v) in self.starting_vec.iter().enumerate() {
+            writeln!(csv, "start,{i},{v}").unwrap();
+        }
+        let mut phases = self.phase_boundaries.iter().peekable();
+        for (n, op) in self.operations.iter().enumerate() {
+            while phases.next_if(|&&p| p == n).is_some() {
+                csv.push_str("phase,,\n");
+            }
+            match op {
+                Operation::Get(i) => writeln!(csv, "get,{i},"),
+                Operation::Set(i, v) => writeln!(csv, "set,{i},{v}"),
+                Operation::Swap(i, j) => writeln!(csv, "swap,{i},{j}"),
+            }
+            .unwrap();
+        }
+        for _ in phases {
+            csv.push_str("phase,,\n");
+        }
+        csv
+    }
+
+    /// See the readme for the layout
+    pub fn to_binary(&self) -> Vec<u8> {
+        let ops = self.operations.to_bytes();
+        let mut out = Vec::with_capacity(32 + self.starting_vec.len() * 4 + ops.len());
+        out.extend_from_slice(BINARY_MAGIC);
+        out.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.length as u32).to_le_bytes());
+        for &v in &self.starting_vec {
+            out.extend_from_slice(&(v as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&(self.phase_boundaries.len() as u64).to_le_bytes());
+        for &p in &self.phase_boundaries {
+            out.extend_from_slice(&(p as u64).to_le_bytes());
+        }
+        out.extend_from_slice(&(self.operations.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(ops.len() as u64).to_le_bytes());
+        out.extend_from_slice(&ops);
+        out
+    }
+}
+
+/// Checks that `op` only touches (and writes values) within `0..length`
+fn check_op(op: Operation, length: usize) -> Result<(), String> {
+    let check = |x: usize, what: &str| {
+        if x < length {
+            Ok(())
+        } else {
+            Err(format!("{what} {x} out of range for length {length}"))
+        }
+    };
+    match op {
+        Operation::Get(i) => check(i, "index"),
+        Operation::Set(i, v) => check(i, "index").and(check(v, "value")),
+        Operation::Swap(i, j) => check(i, "index").and(check(j, "index")),
+    }
+}
+
+impl Trace {
+    /// Reads a trace in any of the `TraceFormat`s, telling them apart by their first bytes
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(BINARY_MAGIC) {
+            return Self::from_binary(bytes);
+        }
+        let text = std::str::from_utf8(bytes).context("not a binary trace and not valid utf-8 text")?;
+        if text.trim_start().starts_with('{') {
+            Self::from_json(text)
+        } else {
+            Self::from_csv(text)
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let json: JsonTrace = serde_json::from_str(text)
+            .map_err(|e| anyhow!("line {}, column {}: {}", e.line(), e.column(), e))?;
+        if json.version != JSON_VERSION {
+            bail!("unsupported json trace version {}", json.version);
+        }
+        if let Err((index, e)) = check_starting_vec(&json.starting_vec, json.length) {
+            let located = JsonLocations::new(text);
+            // The value at fault, or the whole vector if it is the wrong size
+            let at = index.map_or(located.starting_vec, |i| located.starting_values()[i]);
+            bail!("{}: starting_vec: {e}", line_column(text, at));
+        }
+        let mut operations = OpLog::new();
+        for (n, op) in json.operations.into_iter().enumerate() {
+            let op = match op {
+                JsonOp::Get { i } => Operation::Get(i),
+                JsonOp::Set { i, value } => Operation::Set(i, value),
+                JsonOp::Swap { i, j } => Operation::Swap(i, j),
+            };
+            check_op(op, json.length)
+                .map_err(|e| anyhow!("{}: {e}", line_column(text, JsonLocations::new(text).operations[n])))?;
+            operations.push(op);
+        }
+        if let Err((k, e)) = check_phase_boundaries(&json.phase_boundaries, operations.len()) {
+            bail!("{}: {e}", line_column(text, JsonLocations::new(text).phase_boundaries[k]));
+        }
+        Ok(Self { length: json.length, starting_vec: json.starting_vec, operations, phase_boundaries: json.phase_boundaries })
+    }
+
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut starting_vec = vec![];
+        // Line of each `start` row, to point at a bad starting value
+        let mut start_lines = vec![];
+        let mut phase_boundaries = vec![];
+        // Ops with their line, checked once every `start` row (and so the length) is known
+        let mut rows = vec![];
+
+        for (n, line) in text.lines().enumerate() {
+            let line_no = n + 1;
+            let line = line.trim();
+            if line.is_empty() || (n == 0 && line.starts_with("op,")) {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [op, i, x] = fields[..] else {
+                bail!("line {line_no}: expected 3 fields `op,i,j/value`, found {}", fields.len());
+            };
+            let num = |field: &str, what: &str| {
+                field.parse::<usize>().map_err(|_| anyhow!("line {line_no}: invalid {what} `{field}`"))
+            };
+            match op {
+                "start" => {
+                    if !rows.is_empty() {
+                        bail!("line {line_no}: `start` rows must come before any operation");
+                    }
+                    if num(i, "index")? != starting_vec.len() {
+                        bail!("line {line_no}: `start` rows must be in index order");
+                    }
+                    starting_vec.push(num(x, "value")?);
+                    start_lines.push(line_no);
+                }
+                "phase" => phase_boundaries.push(rows.len()),
+                "get" => rows.push((line_no, Operation::Get(num(i, "index")?))),
+                "set" => rows.push((line_no, Operation::Set(num(i, "index")?, num(x, "value")?))),
+                "swap" => rows.push((line_no, Operation::Swap(num(i, "index")?, num(x, "index")?))),
+                other => bail!("line {line_no}: unknown op `{other}`"),
+            }
+        }
+
+        let length = starting_vec.len();
+        if let Err((index, e)) = check_starting_vec(&starting_vec, length) {
+            match index {
+                Some(i) => bail!("line {}: {e}", start_lines[i]),
+                None => bail!("{e}"),
+            }
+        }
+        let mut operations = OpLog::new();
+        for (line_no, op) in rows {
+            check_op(op, length).map_err(|e| anyhow!("line {line_no}: {e}"))?;
+            operations.push(op);
+        }
+        // Phase rows mark the op after them, so they are always in order and in range
+        Ok(Self { length, starting_vec, operations, phase_boundaries })
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self> {
+        let mut r = Reader { bytes, pos: BINARY_MAGIC.len() };
+        let version = r.u16()?;
+        if version != BINARY_VERSION {
+            bail!("byte {}: unsupported binary trace version {version}", r.pos - 2);
+        }
+        let length = r.u32()? as usize;
+        let values_start = r.pos;
+        let starting_vec = (0..length).map(|_| r.u32().map(|v| v as usize)).collect::<Result<Vec<_>>>()?;
+        if let Err((index, e)) = check_starting_vec(&starting_vec, length) {
+            // The value at fault, or the length before the values
+            bail!("byte {}: {e}", index.map_or(values_start - 4, |i| values_start + i * 4));
+        }
+        let phase_count = r.u64()?;
+        let phases_start = r.pos;
+        let phase_boundaries = (0..phase_count).map(|_| r.u64().map(|p| p as usize)).collect::<Result<Vec<_>>>()?;
+        let op_count = r.u64()? as usize;
+        let data_len = r.u64()? as usize;
+        let data_start = r.pos;
+        let data = r.take(data_len)?;
+        if r.pos != bytes.len() {
+            bail!("byte {}: {} unexpected bytes after the operation data", r.pos, bytes.len() - r.pos);
+        }
+
+        let operations = OpLog::from_bytes(data, |op| check_op(op, length))
+            .map_err(|(offset, e)| anyhow!("byte {}: {e}", data_start + offset))?;
+        if operations.len() != op_count {
+            bail!("header says {op_count} operations but the data holds {}", operations.len());
+        }
+        check_phase_boundaries(&phase_boundaries, operations.len())
+            .map_err(|(k, e)| anyhow!("byte {}: {e}", phases_start + k * 8))?;
+        Ok(Self { length, starting_vec, operations, phase_boundaries })
+    }
+}
+
+/// Checks the starting vector against `length`, giving the index of the value at fault if there is one
+fn check_starting_vec(starting_vec: &[usize], length: usize) -> Result<(), (Option<usize>, String)> {
+    if length == 0 {
+        return Err((None, "the trace is empty, length must be at least 1".to_string()));
+    }
+    if starting_vec.len() != length {
+        return Err((None, format!("length is {length} but the starting vector has {} elements", starting_vec.len())));
+    }
+    match starting_vec.iter().position(|&v| v >= length) {
+        Some(i) => Err((Some(i), format!("starting value {} at index {i} out of range for length {length}", starting_vec[i]))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that phase boundaries are ascending and within `op_count` ops, giving the position of
+/// the first that isn't
+fn check_phase_boundaries(phase_boundaries: &[usize], op_count: usize) -> Result<(), (usize, String)> {
+    for (k, &p) in phase_boundaries.iter().enumerate() {
+        if p > op_count {
+            return Err((k, format!("phase boundary {p} is past the {op_count} operations")));
+        }
+        if k > 0 && p < phase_boundaries[k - 1] {
+            return Err((k, format!("phase boundary {p} is before the previous one")));
+        }
+    }
+    Ok(())
+}
+
+/// Little endian reads that report the offset of anything missing
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            bail!("byte {}: unexpected end of file, wanted {n} more bytes", self.pos);
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+/// Writes the trace into the working directory, returning the file name
+#[cfg(not(target_family = "wasm"))]
+pub fn save(trace: &Trace, format: TraceFormat, name: &str) -> Result<String> {
+    let path = format!("{}.{}", name, format.extension());
+    std::fs::write(&path, trace.encode(format))?;
+    Ok(path)
+}
+
+#[cfg(target_family = "wasm")]
+thread_local!(static PICKED: std::cell::RefCell<Option<(String, Vec<u8>)>> = Default::default());
+
+/// Asks the browser for a trace file, which `take_picked` returns once it has been read
+#[cfg(target_family = "wasm")]
+pub fn pick_file() {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+
+    let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+        return;
+    };
+    let Ok(input) = document.create_element("input") else {
+        return;
+    };
+    let input: web_sys::HtmlInputElement = input.unchecked_into();
+    input.set_type("file");
+    input.set_accept(".json,.csv,.strace");
+
+    let picked = input.clone();
+    let on_change = Closure::once(move || {
+        let Some(file) = picked.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            let Ok(buffer) = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await else {
+                return;
+            };
+            let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+            PICKED.with(|p| p.borrow_mut().replace((file.name(), bytes)));
+        });
+    });
+    input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+    on_change.forget();
+    input.click();
+}
+
+#[cfg(target_family = "wasm")]
+pub fn take_picked() -> Option<(String, Vec<u8>)> {
+    PICKED.with(|p| p.borrow_mut().take())
+}
+
+/// Offers the trace to the browser as a file download, returning the file name
+#[cfg(target_family = "wasm")]
+pub fn save(trace: &Trace, format: TraceFormat, name: &str) -> Result<String> {
+    use wasm_bindgen::JsCast;
+    let err = |e: wasm_bindgen::JsValue| anyhow!("{:?}", e);
+
+    let bytes = js_sys::Uint8Array::from(trace.encode(format).as_slice());
+    let parts = js_sys::Array::of1(&bytes);
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_(format.mime());
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options).map_err(err)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(err)?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| anyhow!("no document"))?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a").map_err(err)?.unchecked_into();
+    anchor.set_href(&url);
+    let file_name = format!("{}.{}", name, format.extension());
+    anchor.set_download(&file_name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url).map_err(err)?;
+    Ok(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sketch::algorithms::mergesort;
+    use crate::sketch::trace::block_on;
+    use crate::sketch::{shuffle_step_by_step, List};
+    use strum::IntoEnumIterator;
+
+    fn sorted_trace() -> Trace {
+        let mut list = List::new((0..37).collect(), 37);
+        block_on(shuffle_step_by_step(&mut list)).unwrap();
+        block_on(mergesort::sort(&mut list)).unwrap();
+        Trace {
+            length: 37,
+            starting_vec: (0..37).collect(),
+            operations: list.record_of_operations,
+            phase_boundaries: list.phase_boundaries,
+        }
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        let trace = sorted_trace();
+        for format in TraceFormat::iter() {
+            let decoded = Trace::decode(&trace.encode(format)).unwrap();
+            assert_eq!(decoded.starting_vec, trace.starting_vec, "{format}");
+            assert_eq!(decoded.phase_boundaries, trace.phase_boundaries, "{format}");
+            assert!(decoded.operations.iter().eq(trace.operations.iter()), "{format}");
+        }
+    }
+
+    #[test]
+    fn reports_where_a_trace_is_malformed() {
+        let err = |bytes: &[u8]| format!("{:#}", Trace::decode(bytes).unwrap_err());
+
+        let csv = "op,i,j/value\nstart,0,1\nstart,1,0\nswap,0,1\nget,2,\n";
+        assert_eq!(err(csv.as_bytes()), "line 5: index 2 out of range for length 2");
+        assert_eq!(err(b"start,0,0\nsort,0,0\n"), "line 2: unknown op `sort`");
+        assert_eq!(err(b"start,0,1\nstart,1,2\n"), "line 2: starting value 2 at index 1 out of range for length 2");
+
+        let json = "{\"version\": 1, \"length\": 1,\n \"starting_vec\": [0],\n \"operations\": [{\"op\": \"get\"}]}";
+        assert!(err(json.as_bytes()).starts_with("line 3, column"));
+        let json = "{\"version\": 1, \"length\": 1, \"starting_vec\": [0],\n \"operations\": [{\"op\": \"get\", \"i\": 0}, {\"op\": \"set\", \"i\": 0, \"value\": 4}]}";
+        assert_eq!(err(json.as_bytes()), "line 2, column 40: value 4 out of range for length 1");
+        let json = "{\"version\": 1, \"length\": 2,\n \"starting_vec\": [0, 2], \"operations\": []}";
+        assert_eq!(err(json.as_bytes()), "line 2, column 22: starting_vec: starting value 2 at index 1 out of range for length 2");
+        let json = "{\"version\": 1, \"length\": 1, \"starting_vec\": [0],\n \"phase_boundaries\": [0, 2], \"operations\": []}";
+        assert_eq!(err(json.as_bytes()), "line 2, column 26: phase boundary 2 is past the 0 operations");
+
+        let binary = sorted_trace().to_binary();
+        assert!(err(&binary[..binary.len() - 1]).starts_with("byte "));
+        let single_get = Trace {
+            length: 2,
+            starting_vec: vec![1, 0],
+            operations: [Operation::Get(0)].into_iter().collect(),
+            phase_boundaries: vec![],
+        };
+        let mut bad_index = single_get.to_binary();
+        let data_start = bad_index.len() - 1;
+        // Zigzag delta of 5, get tag
+        bad_index[data_start] = 10 << 2;
+        assert_eq!(err(&bad_index), format!("byte {data_start}: index 5 out of range for length 2"));
+        // Magic, version and length come before the starting values
+        let mut bad_start = single_get.to_binary();
+        bad_start[18] = 7;
+        assert_eq!(err(&bad_start), "byte 18: starting value 7 at index 1 out of range for length 2");
+        let mut bad_phase = Trace { phase_boundaries: vec![1, 0], ..single_get.clone() }.to_binary();
+        bad_phase[30] = 0;
+        bad_phase[38] = 2;
+        assert_eq!(err(&bad_phase), "byte 38: phase boundary 2 is past the 1 operations");
+        // Zigzag delta of 0, run of gets tag, then a varint count of 2^32
+        let long_run_data = [0b11, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let mut long_run = single_get.to_binary();
+        long_run.truncate(data_start - 8);
+        long_run.extend_from_slice(&(long_run_data.len() as u64).to_le_bytes());
+        long_run.extend_from_slice(&long_run_data);
+        assert_eq!(err(&long_run), format!("byte {data_start}: invalid run length"));
+    }
+}